@@ -58,32 +58,38 @@ defmodule BamlElixir.Client do
     - `args`: A map of arguments to pass to the function
     - `opts`: A map of options
       - `path`: The path to the BAML source file
+      - `runtime`: A `BamlElixir.Runtime` to use instead of compiling `path` on every call
+      - `files`: A map of file name to BAML source, used instead of `path`
       - `collectors`: A list of collectors to use
       - `llm_client`: The name of the LLM client to use
+      - `async`: When `true`, the request runs on a tokio runtime inside the NIF instead
+        of blocking a dirty IO scheduler for the whole LLM call
+      - `on_tick`: A function called with `%{raw_text: ..., function_log: ..., thinking: [...]}`
+        as the response arrives. Returning `:abort` cancels the request. The request is
+        streamed under the hood when this is set
+      - `tags`: A map of string tags recorded with the function log, e.g.
+        `%{"user_id" => "123"}`. See `BamlElixir.Collector.logs/2`
+      - `meta`: When `true`, results are returned as `{:ok, result, %{trace_id: id}}`.
+        `id` is the `"id"` of the call's function log in collectors
 
   ## Returns
     - `{:ok, term()}` on success, where the term is the function's return value
-    - `{:error, String.t()}` on failure, with an error message
+    - `{:ok, term(), map()}` on success when `meta: true` is given
+    - `{:error, BamlElixir.Error.t()}` on failure, see `BamlElixir.Error`
 
   ## Examples
       {:ok, result} = BamlElixir.Client.call(client, "MyFunction", %{arg1: "value"})
   """
   @spec call(String.t(), map(), map()) ::
-          {:ok, term()} | {:error, String.t()}
+          {:ok, term()} | {:ok, term(), map()} | {:error, BamlElixir.Error.t()}
   def call(function_name, args, opts \\ %{}) do
-    {path, collectors, client_registry, tb} = prepare_opts(opts)
+    {runtime, collectors, client_registry, tb} = prepare_opts(opts)
     args = to_map(args)
 
-    with {:ok, result} <-
-           native_module().call(function_name, args, path, collectors, client_registry, tb) do
-      result =
-        if opts[:parse] != false do
-          parse_result(result, opts[:prefix], tb)
-        else
-          result
-        end
-
-      {:ok, result}
+    case call_native(function_name, args, runtime, collectors, client_registry, tb, opts) do
+      {:ok, result} -> {:ok, maybe_parse_result(result, opts, tb)}
+      {:ok, result, meta} -> {:ok, maybe_parse_result(result, opts, tb), meta}
+      error -> error
     end
   end
 
@@ -96,8 +102,20 @@ defmodule BamlElixir.Client do
     - `callback`: A function that will be called with the result of the function
     - `opts`: A map of options
       - `path`: The path to the BAML source file
+      - `runtime`: A `BamlElixir.Runtime` to use instead of compiling `path` on every call
+      - `files`: A map of file name to BAML source, used instead of `path`
       - `collectors`: A list of collectors to use
       - `llm_client`: The name of the LLM client to use
+      - `async`: When `true`, the stream runs on a tokio runtime inside the NIF instead
+        of blocking a dirty IO scheduler for the whole LLM call
+      - `stream_state`: When `true`, every value in a partial result is wrapped as
+        `%{value: value, state: :pending | :incomplete | :complete}`. Without it, only
+        fields annotated with `@stream.with_state` are wrapped
+      - `on_tick`: A function called with `%{raw_text: ..., function_log: ..., thinking: [...]}`
+        as the response arrives. Returning `:abort` cancels the stream
+      - `tags`: A map of string tags recorded with the function log
+      - `meta`: When `true`, the callback receives `{:done, result, %{trace_id: id}}`
+        instead of `{:done, result}`
 
   """
   def stream(function_name, args, callback, opts \\ %{}) do
@@ -125,13 +143,14 @@ defmodule BamlElixir.Client do
       end)
 
       start_sync_stream(self(), ref, function_name, args, tripwire, opts)
-      handle_stream_result(ref, callback, opts)
+      handle_stream_result(ref, callback, tripwire, opts)
     end)
   end
 
   @doc """
   Streams partial output and also blocks until the function is done.
-  Finally returns {:ok, result} or {:error, error}
+  Finally returns {:ok, result} or {:error, error}, or {:ok, result, meta} when
+  `meta: true` is given
   """
   def sync_stream(function_name, args, callback, opts \\ %{}) do
     pid = self()
@@ -155,6 +174,9 @@ defmodule BamlElixir.Client do
 
       {:done, {:done, result}} ->
         {:ok, result}
+
+      {:done, {:done, result, meta}} ->
+        {:ok, result, meta}
     end
   end
 
@@ -212,8 +234,127 @@ defmodule BamlElixir.Client do
     end
   end
 
+  # The NIF runs in a separate process (or on the NIF's tokio runtime in async mode)
+  # while the caller waits for the result, so that the caller dying, e.g. through
+  # `Task.shutdown/2`, can abort the HTTP request through the tripwire.
+  defp call_native(function_name, args, runtime, collectors, client_registry, tb, opts) do
+    native = native_module()
+    tripwire = native.create_tripwire()
+    ref = make_ref()
+    caller_pid = self()
+
+    # The NIF process is monitored rather than linked, so that it raising, e.g. on
+    # an argument it cannot decode, raises in the caller instead of killing it
+    monitor =
+      if opts[:async] do
+        case native.call_async(
+               caller_pid,
+               ref,
+               tripwire,
+               function_name,
+               args,
+               runtime,
+               collectors,
+               client_registry,
+               tb,
+               native_options(opts)
+             ) do
+          :ok -> :ok
+          error -> send(caller_pid, {ref, error})
+        end
+
+        nil
+      else
+        {_pid, monitor} =
+          spawn_monitor(fn ->
+            result =
+              native.call(
+                caller_pid,
+                ref,
+                tripwire,
+                function_name,
+                args,
+                runtime,
+                collectors,
+                client_registry,
+                tb,
+                native_options(opts)
+              )
+
+            send(caller_pid, {ref, result})
+          end)
+
+        monitor
+      end
+
+    watcher =
+      spawn(fn ->
+        caller_ref = Process.monitor(caller_pid)
+
+        receive do
+          {:DOWN, ^caller_ref, :process, ^caller_pid, _reason} ->
+            native.abort_tripwire(tripwire)
+
+          :done ->
+            :ok
+        end
+      end)
+
+    try do
+      await_native_result(ref, monitor, tripwire, opts)
+    after
+      send(watcher, :done)
+    end
+  end
+
+  defp await_native_result(ref, monitor, tripwire, opts) do
+    receive do
+      {^ref, {:tick, info}} ->
+        handle_tick(info, tripwire, opts)
+        await_native_result(ref, monitor, tripwire, opts)
+
+      {^ref, result} ->
+        if monitor, do: Process.demonitor(monitor, [:flush])
+        result
+
+      # The NIF process only exits before sending its result when it crashed
+      {:DOWN, ^monitor, :process, _pid, {exception, stacktrace}}
+      when is_exception(exception) ->
+        reraise exception, stacktrace
+
+      {:DOWN, ^monitor, :process, _pid, reason} ->
+        exit(reason)
+    end
+  end
+
+  defp handle_tick(info, tripwire, opts) do
+    if opts[:on_tick].(info) == :abort do
+      native_module().abort_tripwire(tripwire)
+    end
+  end
+
+  defp start_sync_stream(pid, ref, function_name, args, tripwire, %{async: true} = opts) do
+    {runtime, collectors, client_registry, tb} = prepare_opts(opts)
+
+    with {:error, _} = error <-
+           native_module().stream_async(
+             pid,
+             ref,
+             tripwire,
+             function_name,
+             args,
+             runtime,
+             collectors,
+             client_registry,
+             tb,
+             native_options(opts)
+           ) do
+      send(pid, {ref, error})
+    end
+  end
+
   defp start_sync_stream(pid, ref, function_name, args, tripwire, opts) do
-    {path, collectors, client_registry, tb} = prepare_opts(opts)
+    {runtime, collectors, client_registry, tb} = prepare_opts(opts)
 
     spawn_link(fn ->
       result =
@@ -223,41 +364,43 @@ defmodule BamlElixir.Client do
           tripwire,
           function_name,
           args,
-          path,
+          runtime,
           collectors,
           client_registry,
-          tb
+          tb,
+          native_options(opts)
         )
 
       send(pid, {ref, result})
     end)
   end
 
-  defp handle_stream_result(ref, callback, opts) do
+  defp handle_stream_result(ref, callback, tripwire, opts) do
     receive do
-      {^ref, {:partial, result}} ->
-        result =
-          if opts[:parse] != false do
-            parse_result(result, opts[:prefix], opts[:tb])
-          else
-            result
-          end
+      {^ref, {:tick, info}} ->
+        handle_tick(info, tripwire, opts)
+        handle_stream_result(ref, callback, tripwire, opts)
 
-        callback.({:partial, result})
-        handle_stream_result(ref, callback, opts)
+      {^ref, {:partial, result}} ->
+        callback.({:partial, maybe_parse_result(result, opts, opts[:tb])})
+        handle_stream_result(ref, callback, tripwire, opts)
 
       {^ref, {:error, _} = msg} ->
         callback.(msg)
 
       {^ref, {:done, result}} ->
-        result =
-          if opts[:parse] != false do
-            parse_result(result, opts[:prefix], opts[:tb])
-          else
-            result
-          end
+        callback.({:done, maybe_parse_result(result, opts, opts[:tb])})
 
-        callback.({:done, result})
+      {^ref, {:done, result, meta}} ->
+        callback.({:done, maybe_parse_result(result, opts, opts[:tb]), meta})
+    end
+  end
+
+  defp maybe_parse_result(result, opts, tb) do
+    if opts[:parse] != false do
+      parse_result(result, opts[:prefix], tb)
+    else
+      result
     end
   end
 
@@ -328,7 +471,9 @@ defmodule BamlElixir.Client do
       quote do
         defmodule unquote(module_name) do
           @spec call(%{unquote_splicing(param_types)}, map()) ::
-                  {:ok, unquote(return_type)} | {:error, String.t()}
+                  {:ok, unquote(return_type)}
+                  | {:ok, unquote(return_type), map()}
+                  | {:error, BamlElixir.Error.t()}
           def call(args, opts \\ %{}) do
             opts =
               opts
@@ -340,10 +485,14 @@ defmodule BamlElixir.Client do
 
           @spec stream(
                   %{unquote_splicing(param_types)},
-                  ({:ok, unquote(return_type) | {:error, String.t()} | :done} -> any()),
+                  ({:partial, unquote(return_type)}
+                   | {:done, unquote(return_type)}
+                   | {:done, unquote(return_type), map()}
+                   | {:error, BamlElixir.Error.t()} ->
+                     any()),
                   map()
                 ) ::
-                  Enumerable.t()
+                  pid()
           def stream(args, callback, opts \\ %{}) do
             opts =
               opts
@@ -357,7 +506,10 @@ defmodule BamlElixir.Client do
                   %{unquote_splicing(param_types)},
                   (unquote(return_type) -> any()),
                   map()
-                ) :: {:ok, unquote(return_type)} | {:error, String.t()}
+                ) ::
+                  {:ok, unquote(return_type)}
+                  | {:ok, unquote(return_type), map()}
+                  | {:error, BamlElixir.Error.t()}
           def sync_stream(args, callback, opts \\ %{}) do
             opts =
               opts
@@ -397,6 +549,13 @@ defmodule BamlElixir.Client do
                 | %{url: String.t(), media_type: String.t()}
                 | %{base64: String.t()}
                 | %{base64: String.t(), media_type: String.t()}
+                | BamlElixir.Media.t()
+                | %{
+                    required(:__baml_media__) => :image | :audio | :pdf | :video,
+                    optional(:url) => String.t() | nil,
+                    optional(:base64) => String.t() | nil,
+                    optional(:media_type) => String.t() | nil
+                  }
             )
         end
 
@@ -474,7 +633,19 @@ defmodule BamlElixir.Client do
   end
 
   defp prepare_opts(opts) do
-    path = opts[:path] || "baml_src"
+    runtime =
+      case opts[:runtime] do
+        %BamlElixir.Runtime{reference: reference} ->
+          reference
+
+        nil ->
+          opts[:files] || opts[:path] || "baml_src"
+
+        other ->
+          raise ArgumentError,
+                "expected :runtime to be a %BamlElixir.Runtime{}, got: #{inspect(other)}"
+      end
+
     collectors = (opts[:collectors] || []) |> Enum.map(fn collector -> collector.reference end)
 
     client_registry =
@@ -488,7 +659,16 @@ defmodule BamlElixir.Client do
         end
       end
 
-    {path, collectors, client_registry, opts[:tb]}
+    {runtime, collectors, client_registry, opts[:tb]}
+  end
+
+  defp native_options(opts) do
+    %{
+      stream_state: opts[:stream_state] == true,
+      tick: is_function(opts[:on_tick], 1),
+      tags: opts[:tags],
+      meta: opts[:meta] == true
+    }
   end
 
   # If type builder is provided, return as map instead of struct
@@ -507,6 +687,13 @@ defmodule BamlElixir.Client do
     String.to_atom(value)
   end
 
+  # Values with `@check` constraints or a stream state are wrapped with their
+  # check results and completion state
+  defp parse_result(%{:value => value} = wrapped, prefix, tb)
+       when is_map_key(wrapped, :checks) or is_map_key(wrapped, :state) do
+    %{wrapped | value: parse_result(value, prefix, tb)}
+  end
+
   defp parse_result(list, prefix, tb) when is_list(list) do
     Enum.map(list, fn item -> parse_result(item, prefix, tb) end)
   end
@@ -515,6 +702,10 @@ defmodule BamlElixir.Client do
     result
   end
 
+  defp to_map(%BamlElixir.Media{} = media) do
+    BamlElixir.Media.to_map(media)
+  end
+
   defp to_map(args) when is_struct(args) do
     args
     |> Map.from_struct()
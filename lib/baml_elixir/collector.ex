@@ -1,8 +1,41 @@
 defmodule BamlElixir.Collector do
   defstruct reference: nil
 
-  def new(name) when is_binary(name) do
-    reference = BamlElixir.Native.collector_new(name)
+  @doc """
+  Creates a collector.
+
+  ## Options
+    - `max_logs`: Only keep the logs of the last `max_logs` requests, so that a long-lived
+      collector can be used as a rolling window
+    - `body`: How HTTP request and response bodies are returned. `:json` (default) decodes
+      JSON bodies and returns other bodies as text, `:text` returns them as text and `:raw`
+      returns the exact bytes as a binary
+    - `max_body_bytes`: Only return the first `max_body_bytes` of each body. `body_size` and
+      `body_truncated` tell whether a body was cut. Body paths are redacted before bodies are
+      cut, and bodies to cut that can't be redacted are returned as `"[REDACTED]"`
+    - `redact`: Credential headers such as `authorization` and `x-api-key` and API keys in
+      URLs are replaced with `"[REDACTED]"` unless this is `false`
+    - `redact_headers`: Additional header names to redact
+    - `redact_body_paths`: Paths of JSON body values to redact, e.g. `["metadata.user_id"]`.
+      Lists along the path are redacted element by element, also in the bodies of HTTP
+      errors. Raw bodies can't be redacted, so `body: :raw` raises unless `redact` is `false`
+    - `prices`: Prices in USD per million tokens by provider and model, e.g.
+      `%{"openai" => %{"gpt-4o" => %{input: 2.5, cached_input: 1.25, output: 10}}}`.
+      When given, `usage/1` and the usage of function logs and calls include a `"cost"`
+      in USD. Cached input tokens are billed at the input price unless `cached_input` is given.
+      They are taken out of the input tokens, except for `"anthropic"` and `"aws-bedrock"`,
+      which report them separately. Set `cached_in_input` on a price to override this
+  """
+  def new(name, opts \\ %{}) when is_binary(name) do
+    opts = Map.new(opts)
+
+    redacts_body? = opts[:redact] != false and opts[:redact_body_paths] not in [nil, []]
+
+    if opts[:body] == :raw and redacts_body? do
+      raise ArgumentError, "`body: :raw` can't be combined with `redact_body_paths`"
+    end
+
+    reference = BamlElixir.Native.collector_new(name, opts)
     %__MODULE__{reference: reference}
   end
 
@@ -13,4 +46,38 @@ defmodule BamlElixir.Collector do
   def last_function_log(%__MODULE__{reference: reference}) when is_reference(reference) do
     BamlElixir.Native.collector_last_function_log(reference)
   end
+
+  @doc """
+  Returns every function log recorded by the collector, oldest first.
+
+  When `tags` is given, only logs of calls made with all of those tags are returned:
+
+      BamlElixir.Collector.logs(collector, %{"user_id" => "123"})
+  """
+  def logs(%__MODULE__{reference: reference}, tags \\ %{})
+      when is_reference(reference) and is_map(tags) do
+    BamlElixir.Native.collector_logs(reference, tags)
+  end
+
+  @doc """
+  Returns the function log with the given `"id"`, or `nil` if the collector has none.
+  """
+  def log_by_id(%__MODULE__{reference: reference}, id)
+      when is_reference(reference) and is_binary(id) do
+    BamlElixir.Native.collector_log_by_id(reference, id)
+  end
+
+  @doc """
+  Removes every log from the collector and returns how many were removed.
+  """
+  def clear(%__MODULE__{reference: reference}) when is_reference(reference) do
+    BamlElixir.Native.collector_clear(reference)
+  end
+
+  @doc """
+  Removes every log from the collector and returns them, oldest first.
+  """
+  def drain(%__MODULE__{reference: reference}) when is_reference(reference) do
+    BamlElixir.Native.collector_drain(reference)
+  end
 end
@@ -0,0 +1,53 @@
+defmodule BamlElixir.Error do
+  @moduledoc """
+  Errors returned by BAML function calls as `{:error, error}`.
+
+  Every error is a map with a `:type` and a `:message`. Depending on the type, it carries
+  more information:
+
+    - `:validation_error` - the LLM output could not be parsed into the return type.
+      Includes `:raw_output`, `:prompt`, `:client_name`, `:model`, the parser's
+      `:diagnostics` (see `t:diagnostic/0`) and `:detailed_message`.
+    - `:finish_reason_error` - the LLM stopped for a finish reason that is not allowed.
+      Includes `:raw_output`, `:prompt`, `:finish_reason` and `:detailed_message`.
+    - `:client_http_error` - the LLM provider returned an error. Includes `:client_name`,
+      `:status_code` and `:detailed_message`.
+    - `:timeout_error` - the request to the LLM provider timed out. Includes `:client_name`.
+    - `:cancelled` - the request was aborted, e.g. because the caller exited.
+    - `:invalid_argument` - the arguments or options passed to the call are invalid.
+    - `:configuration_error` - the BAML project could not be compiled.
+    - `:type_builder_error` - BAML source given as `%BamlElixir.TypeBuilder.Baml{}` could
+      not be compiled. `:diagnostics` lists the compiler's diagnostics.
+    - `:internal_error` - any other failure. Includes `:detailed_message`.
+  """
+
+  @type error_type ::
+          :validation_error
+          | :finish_reason_error
+          | :client_http_error
+          | :timeout_error
+          | :cancelled
+          | :invalid_argument
+          | :configuration_error
+          | :type_builder_error
+          | :internal_error
+
+  @type diagnostic :: %{
+          severity: :error | :warning,
+          message: String.t(),
+          line: non_neg_integer() | nil
+        }
+
+  @type t :: %{
+          required(:type) => error_type(),
+          required(:message) => String.t(),
+          optional(:raw_output) => String.t(),
+          optional(:prompt) => String.t(),
+          optional(:client_name) => String.t() | nil,
+          optional(:model) => String.t() | nil,
+          optional(:diagnostics) => [diagnostic()] | nil,
+          optional(:status_code) => non_neg_integer(),
+          optional(:finish_reason) => String.t() | nil,
+          optional(:detailed_message) => String.t()
+        }
+end
@@ -0,0 +1,57 @@
+defmodule BamlElixir.Media do
+  @moduledoc """
+  Media arguments (`image`, `audio`, `pdf` and `video`) for BAML functions.
+
+  Media can come from a URL, base64 encoded data or a local file. Files are read and
+  sent as base64. When `media_type` is not given, it is inferred from the file or URL
+  extension, or from the data URL prefix of base64 data.
+
+      MyApp.BamlClient.DescribeImage.call(%{
+        myImg: BamlElixir.Media.file(:image, "priv/images/shrek.png")
+      })
+
+  Media is validated before the request is sent, so a missing file or a media type that
+  doesn't match the kind of media is returned as an `:invalid_argument` error.
+  """
+
+  defstruct [:type, :url, :base64, :file, :media_type]
+
+  @type media_kind :: :image | :audio | :pdf | :video
+
+  @type t :: %__MODULE__{
+          type: media_kind(),
+          url: String.t() | nil,
+          base64: String.t() | nil,
+          file: Path.t() | nil,
+          media_type: String.t() | nil
+        }
+
+  @doc "Media loaded by the LLM provider from a URL."
+  @spec url(media_kind(), String.t(), String.t() | nil) :: t()
+  def url(type, url, media_type \\ nil) do
+    %__MODULE__{type: type, url: url, media_type: media_type}
+  end
+
+  @doc "Media from base64 encoded data or a base64 data URL."
+  @spec base64(media_kind(), String.t(), String.t() | nil) :: t()
+  def base64(type, base64, media_type \\ nil) do
+    %__MODULE__{type: type, base64: base64, media_type: media_type}
+  end
+
+  @doc "Media read from a local file."
+  @spec file(media_kind(), Path.t(), String.t() | nil) :: t()
+  def file(type, path, media_type \\ nil) do
+    %__MODULE__{type: type, file: path, media_type: media_type}
+  end
+
+  @doc false
+  def to_map(%__MODULE__{} = media) do
+    %{
+      __baml_media__: media.type,
+      url: media.url,
+      base64: media.base64,
+      file: media.file && Path.expand(media.file),
+      media_type: media.media_type
+    }
+  end
+end
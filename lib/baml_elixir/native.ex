@@ -19,8 +19,19 @@ defmodule BamlElixir.Native do
       "aarch64-unknown-linux-musl"
     ]
 
-  def call(_function_name, _args, _path, _collectors, _client_registry, _tb),
-    do: :erlang.nif_error(:nif_not_loaded)
+  def call(
+        _pid,
+        _reference,
+        _tripwire,
+        _function_name,
+        _args,
+        _runtime,
+        _collectors,
+        _client_registry,
+        _tb,
+        _options
+      ),
+      do: :erlang.nif_error(:nif_not_loaded)
 
   def stream(
         _pid,
@@ -28,22 +39,66 @@ defmodule BamlElixir.Native do
         _tripwire,
         _function_name,
         _args,
-        _path,
+        _runtime,
+        _collectors,
+        _client_registry,
+        _tb,
+        _options
+      ),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+  def call_async(
+        _pid,
+        _reference,
+        _tripwire,
+        _function_name,
+        _args,
+        _runtime,
+        _collectors,
+        _client_registry,
+        _tb,
+        _options
+      ),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+  def stream_async(
+        _pid,
+        _reference,
+        _tripwire,
+        _function_name,
+        _args,
+        _runtime,
         _collectors,
         _client_registry,
-        _tb
+        _tb,
+        _options
       ),
       do: :erlang.nif_error(:nif_not_loaded)
 
   def create_tripwire(), do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc false
+  def spawn_panicking_task(_pid, _reference), do: :erlang.nif_error(:nif_not_loaded)
+
   def abort_tripwire(_tripwire), do: :erlang.nif_error(:nif_not_loaded)
 
-  def collector_new(_name), do: :erlang.nif_error(:nif_not_loaded)
+  def collector_new(_name, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  def collector_clear(_collector), do: :erlang.nif_error(:nif_not_loaded)
+
+  def collector_drain(_collector), do: :erlang.nif_error(:nif_not_loaded)
 
   def collector_usage(_collector), do: :erlang.nif_error(:nif_not_loaded)
 
   def collector_last_function_log(_collector), do: :erlang.nif_error(:nif_not_loaded)
 
-  def parse_baml(_path), do: :erlang.nif_error(:nif_not_loaded)
+  def collector_logs(_collector, _tags), do: :erlang.nif_error(:nif_not_loaded)
+
+  def collector_log_by_id(_collector, _id), do: :erlang.nif_error(:nif_not_loaded)
+
+  def runtime_new(_path, _env, _feature_flags), do: :erlang.nif_error(:nif_not_loaded)
+
+  def runtime_from_files(_files, _env, _feature_flags), do: :erlang.nif_error(:nif_not_loaded)
+
+  def parse_baml(_runtime), do: :erlang.nif_error(:nif_not_loaded)
 end
@@ -11,11 +11,47 @@ defmodule BamlElixir.NativeBehaviour do
               reference(),
               String.t(),
               map(),
-              String.t(),
+              String.t() | reference(),
               list(),
               map() | nil,
-              list() | nil
+              list() | nil,
+              map()
             ) :: any()
-  @callback call(String.t(), map(), String.t(), list(), map() | nil, list() | nil) ::
-              {:ok, any()} | {:error, String.t()}
+  @callback call(
+              pid(),
+              reference(),
+              reference(),
+              String.t(),
+              map(),
+              String.t() | reference(),
+              list(),
+              map() | nil,
+              list() | nil,
+              map()
+            ) ::
+              {:ok, any()} | {:error, BamlElixir.Error.t()}
+  @callback call_async(
+              pid(),
+              reference(),
+              reference(),
+              String.t(),
+              map(),
+              String.t() | reference(),
+              list(),
+              map() | nil,
+              list() | nil,
+              map()
+            ) :: :ok | {:error, BamlElixir.Error.t()}
+  @callback stream_async(
+              pid(),
+              reference(),
+              reference(),
+              String.t(),
+              map(),
+              String.t() | reference(),
+              list(),
+              map() | nil,
+              list() | nil,
+              map()
+            ) :: :ok | {:error, BamlElixir.Error.t()}
 end
@@ -0,0 +1,51 @@
+defmodule BamlElixir.Runtime do
+  @moduledoc """
+  A compiled BAML runtime that can be reused across calls.
+
+  Passing a path to `BamlElixir.Client.call/3` re-parses the whole BAML project on
+  every request. Create a runtime once and pass it as the `:runtime` option instead:
+
+      {:ok, runtime} = BamlElixir.Runtime.new("priv/baml_src")
+      MyApp.BamlClient.ExtractResume.call(%{resume: "..."}, %{runtime: runtime})
+  """
+
+  defstruct reference: nil
+
+  @doc """
+  Compiles the BAML files in `path` into a runtime.
+
+  ## Options
+    - `env`: A map of environment variables visible to the BAML project. Defaults to the
+      system environment, which is read again on every call.
+    - `feature_flags`: A list of BAML feature flags to enable.
+  """
+  def new(path \\ "baml_src", opts \\ %{}) do
+    path = BamlElixir.Client.app_path(path)
+
+    with {:ok, reference} <-
+           BamlElixir.Native.runtime_new(path, opts[:env], opts[:feature_flags] || []) do
+      {:ok, %__MODULE__{reference: reference}}
+    end
+  end
+
+  @doc """
+  Compiles BAML source held in memory, given as a map of file name to contents.
+
+      {:ok, runtime} = BamlElixir.Runtime.from_files(%{"main.baml" => baml_source})
+
+  Accepts the same options as `new/2`.
+  """
+  def from_files(files, opts \\ %{}) when is_map(files) do
+    with {:ok, reference} <-
+           BamlElixir.Native.runtime_from_files(files, opts[:env], opts[:feature_flags] || []) do
+      {:ok, %__MODULE__{reference: reference}}
+    end
+  end
+
+  @doc """
+  Returns the classes, enums and functions defined in the runtime.
+  """
+  def parse(%__MODULE__{reference: reference}) do
+    BamlElixir.Native.parse_baml(reference)
+  end
+end
@@ -1,10 +1,10 @@
 defmodule BamlElixir.TypeBuilder do
   defmodule Class do
-    defstruct [:name, :fields]
+    defstruct [:name, :fields, :description, :alias]
   end
 
   defmodule Enum do
-    defstruct [:name, :values]
+    defstruct [:name, :values, :description, :alias]
   end
 
   defmodule EnumValue do
@@ -12,7 +12,7 @@ defmodule BamlElixir.TypeBuilder do
   end
 
   defmodule Field do
-    defstruct [:name, :type, :description, :alias, :skip]
+    defstruct [:name, :type, :description, :alias, :skip, :constraints]
   end
 
   defmodule Union do
@@ -30,4 +30,24 @@ defmodule BamlElixir.TypeBuilder do
   defmodule List do
     defstruct [:type]
   end
+
+  defmodule Optional do
+    defstruct [:type]
+  end
+
+  defmodule Baml do
+    defstruct [:source]
+  end
+
+  defmodule Constrained do
+    defstruct [:type, :constraints]
+  end
+
+  defmodule Check do
+    defstruct [:name, :expression]
+  end
+
+  defmodule Assert do
+    defstruct [:name, :expression]
+  end
 end
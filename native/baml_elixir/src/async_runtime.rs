@@ -0,0 +1,91 @@
+use crate::errors::BamlError;
+use rustler::env::SavedTerm;
+use rustler::types::atom;
+use rustler::{Encoder, Env, LocalPid, OwnedEnv, Term};
+use std::any::Any;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+/// Runs `future` on the tokio runtime shared by every async NIF call. If it panics,
+/// `{:error, %{type: :internal_error}}` is sent through `reply`, as the caller waits
+/// for a reply without a timeout.
+pub fn spawn<F>(reply: Arc<Reply>, future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let runtime = RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("baml_elixir")
+            .build()
+            .expect("Failed to start the baml_elixir tokio runtime")
+    });
+    let task = runtime.spawn(future);
+    runtime.spawn(async move {
+        if let Err(e) = task.await {
+            if e.is_panic() {
+                let message = format!("BAML task panicked: {}", panic_message(e.into_panic()));
+                reply.send(|env| (atom::error(), BamlError::internal(message)).encode(env));
+            }
+        }
+    });
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// Sends `{reference, message}` tuples to a process from threads that are not
+/// managed by the BEAM.
+pub struct Reply {
+    pid: LocalPid,
+    reference: Mutex<(OwnedEnv, SavedTerm)>,
+}
+
+impl Reply {
+    pub fn new(pid: LocalPid, reference: Term) -> Self {
+        let owned_env = OwnedEnv::new();
+        let saved = owned_env.save(reference);
+        Reply {
+            pid,
+            reference: Mutex::new((owned_env, saved)),
+        }
+    }
+
+    pub fn send<F>(&self, message: F)
+    where
+        F: for<'a> FnOnce(Env<'a>) -> Term<'a>,
+    {
+        self.try_send(|env| Some(message(env)));
+    }
+
+    /// Like `send`, but nothing is sent when `message` returns `None`.
+    pub fn try_send<F>(&self, message: F)
+    where
+        F: for<'a> FnOnce(Env<'a>) -> Option<Term<'a>>,
+    {
+        let mut msg_env = OwnedEnv::new();
+        let Some(message) = msg_env.run(|env| message(env).map(|term| msg_env.save(term))) else {
+            return;
+        };
+        // The lock is poisoned when a task panicked while replying, and it still
+        // has to reply with the panic
+        let guard = self
+            .reference
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (ref_env, saved) = &*guard;
+        let _ = msg_env.send_and_clear(&self.pid, |env| {
+            let reference = ref_env.run(|ref_env| saved.load(ref_env).in_env(env));
+            (reference, message.load(env)).encode(env)
+        });
+    }
+}
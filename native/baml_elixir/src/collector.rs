@@ -1,41 +1,600 @@
+use baml_runtime::internal::llm_client::LLMResponse;
 use baml_runtime::tracingv2::storage::storage::Collector as BamlCollector;
-use rustler::{Encoder, Env, Resource, ResourceArc, Term};
+use baml_runtime::FunctionResult;
+use rustler::types::binary::OwnedBinary;
+use rustler::{Encoder, Env, Error, MapIterator, NifResult, Resource, ResourceArc, Term};
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[rustler::resource_impl()]
 impl Resource for CollectorResource {}
 
+/// Every request gets its own BAML collector so that single logs can be dropped:
+/// clearing, draining or evicting a request releases everything recorded for it.
 pub struct CollectorResource {
-    pub inner: Arc<BamlCollector>,
+    name: Option<String>,
+    options: Arc<CollectorOptions>,
+    requests: Mutex<VecDeque<TrackedRequest>>,
+}
+
+/// How HTTP bodies are returned in function logs.
+#[derive(Clone, Copy, Default)]
+pub enum BodyMode {
+    /// Decoded JSON, falling back to text for bodies that aren't JSON.
+    #[default]
+    Json,
+    Text,
+    /// The body bytes as an Elixir binary.
+    Raw,
+}
+
+/// Prices of a model in USD per million tokens. Cached input tokens are billed at
+/// the input price unless `cached_input` is given.
+pub struct Price {
+    pub input: f64,
+    pub cached_input: Option<f64>,
+    pub output: f64,
+    /// Whether the input tokens reported by the provider include the cached ones.
+    pub cached_in_input: bool,
+}
+
+// Providers that report cache reads next to the input tokens rather than as part of them
+const SEPARATE_CACHED_INPUT_PROVIDERS: &[&str] = &["anthropic", "aws-bedrock"];
+
+impl Price {
+    fn from_term(term: Term, provider: &str) -> NifResult<Price> {
+        let mut input = None;
+        let mut cached_input = None;
+        let mut output = None;
+        let mut cached_in_input = !SEPARATE_CACHED_INPUT_PROVIDERS.contains(&provider);
+        let iter = MapIterator::new(term).ok_or(Error::BadArg)?;
+        for (key_term, value_term) in iter {
+            match key_term.atom_to_string()?.as_str() {
+                "input" => input = Some(decode_number(value_term)?),
+                "cached_input" => cached_input = Some(decode_number(value_term)?),
+                "output" => output = Some(decode_number(value_term)?),
+                "cached_in_input" => cached_in_input = value_term.decode()?,
+                _ => {}
+            }
+        }
+        Ok(Price {
+            input: input.ok_or(Error::BadArg)?,
+            cached_input,
+            output: output.ok_or(Error::BadArg)?,
+            cached_in_input,
+        })
+    }
+
+    fn cost(&self, usage: &baml_runtime::tracingv2::storage::storage::Usage) -> f64 {
+        let input = usage.input_tokens.unwrap_or_default();
+        let cached_input = usage.cached_input_tokens.unwrap_or_default();
+        let output = usage.output_tokens.unwrap_or_default();
+        let uncached_input = if self.cached_in_input {
+            (input - cached_input).max(0)
+        } else {
+            input
+        };
+        (uncached_input as f64 * self.input
+            + cached_input as f64 * self.cached_input.unwrap_or(self.input)
+            + output as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+fn decode_number(term: Term) -> NifResult<f64> {
+    term.decode::<f64>()
+        .or_else(|_| term.decode::<i64>().map(|n| n as f64))
+}
+
+const REDACTED: &str = "[REDACTED]";
+
+// Credential headers sent by the providers BAML supports.
+const CREDENTIAL_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "api-key",
+    "x-goog-api-key",
+    "cookie",
+    "set-cookie",
+];
+
+// Query parameters used to pass API keys, e.g. by Google AI.
+const CREDENTIAL_QUERY_PARAMS: &[&str] = &["key", "api_key", "access_token"];
+
+/// Options given to `collector_new`, e.g. `%{max_logs: 100, body: :raw}`.
+pub struct CollectorOptions {
+    pub max_logs: Option<usize>,
+    pub body: BodyMode,
+    pub max_body_bytes: Option<usize>,
+    /// Whether credentials are redacted from logged requests and responses.
+    pub redact: bool,
+    /// Headers to redact on top of `CREDENTIAL_HEADERS`, lowercased.
+    pub redact_headers: Vec<String>,
+    /// Paths of JSON body values to redact, e.g. `["metadata", "user_id"]`.
+    pub redact_body_paths: Vec<Vec<String>>,
+    /// Prices by provider and model, e.g. `prices["openai"]["gpt-4o"]`.
+    pub prices: HashMap<String, HashMap<String, Price>>,
+}
+
+impl Default for CollectorOptions {
+    fn default() -> Self {
+        CollectorOptions {
+            max_logs: None,
+            body: BodyMode::default(),
+            max_body_bytes: None,
+            redact: true,
+            redact_headers: Vec::new(),
+            redact_body_paths: Vec::new(),
+            prices: HashMap::new(),
+        }
+    }
+}
+
+impl CollectorOptions {
+    pub fn from_term(term: Term) -> NifResult<CollectorOptions> {
+        let mut options = CollectorOptions::default();
+        let iter = MapIterator::new(term).ok_or(Error::BadArg)?;
+        for (key_term, value_term) in iter {
+            let key = key_term.atom_to_string()?;
+            match key.as_str() {
+                "max_logs" => options.max_logs = value_term.decode()?,
+                "max_body_bytes" => options.max_body_bytes = value_term.decode()?,
+                "redact" => options.redact = value_term.decode()?,
+                "redact_headers" => {
+                    options.redact_headers = value_term
+                        .decode::<Vec<String>>()?
+                        .iter()
+                        .map(|header| header.to_ascii_lowercase())
+                        .collect()
+                }
+                "redact_body_paths" => {
+                    options.redact_body_paths = value_term
+                        .decode::<Vec<String>>()?
+                        .iter()
+                        .map(|path| path.split('.').map(|key| key.to_string()).collect())
+                        .collect()
+                }
+                "prices" => {
+                    let providers = MapIterator::new(value_term).ok_or(Error::BadArg)?;
+                    for (provider, models) in providers {
+                        let provider: String = provider.decode()?;
+                        let models = MapIterator::new(models)
+                            .ok_or(Error::BadArg)?
+                            .map(|(model, price)| {
+                                Ok((model.decode()?, Price::from_term(price, &provider)?))
+                            })
+                            .collect::<NifResult<_>>()?;
+                        options.prices.insert(provider, models);
+                    }
+                }
+                "body" => {
+                    options.body = match value_term.atom_to_string()?.as_str() {
+                        "json" => BodyMode::Json,
+                        "text" => BodyMode::Text,
+                        "raw" => BodyMode::Raw,
+                        _ => return Err(Error::BadArg),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+
+    fn redact_headers<'h>(
+        &self,
+        headers: impl Iterator<Item = (&'h String, &'h String)>,
+    ) -> HashMap<String, String> {
+        headers
+            .map(|(name, value)| {
+                let lowercase = name.to_ascii_lowercase();
+                let redact = self.redact
+                    && (CREDENTIAL_HEADERS.contains(&lowercase.as_str())
+                        || self.redact_headers.contains(&lowercase));
+                let value = if redact {
+                    REDACTED.to_string()
+                } else {
+                    value.clone()
+                };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn redact_url(&self, url: &str) -> String {
+        let Some((base, query)) = url.split_once('?').filter(|_| self.redact) else {
+            return url.to_string();
+        };
+        let query: Vec<String> = query
+            .split('&')
+            .map(|param| match param.split_once('=') {
+                Some((key, _)) if CREDENTIAL_QUERY_PARAMS.contains(&key) => {
+                    format!("{}={}", key, REDACTED)
+                }
+                _ => param.to_string(),
+            })
+            .collect();
+        format!("{}?{}", base, query.join("&"))
+    }
+
+    /// The cost of `call` in USD, or `None` if there is no price for its provider and model
+    /// or the provider reported no usage.
+    fn cost(&self, call: &baml_runtime::tracingv2::storage::storage::LLMCall) -> Option<f64> {
+        let price = self.prices.get(&call.provider)?.get(&model_of(call)?)?;
+        let usage = call
+            .usage
+            .as_ref()
+            .filter(|usage| usage.input_tokens.is_some() || usage.output_tokens.is_some())?;
+        Some(price.cost(usage))
+    }
+
+    /// The cost of `calls` in USD. Failed calls, the ones that weren't selected or got an
+    /// HTTP error, are free. The cost is `None` as soon as one of the others has no price
+    /// or no usage.
+    fn calls_cost(
+        &self,
+        calls: &[baml_runtime::tracingv2::storage::storage::LLMCallKind],
+    ) -> Option<f64> {
+        if self.prices.is_empty() {
+            return None;
+        }
+        calls
+            .iter()
+            .map(llm_call)
+            .filter(|call| {
+                call.selected
+                    && call
+                        .response
+                        .as_deref()
+                        .is_none_or(|response| (200..300).contains(&response.status))
+            })
+            .map(|call| self.cost(call))
+            .sum()
+    }
+
+    fn redacts_body(&self) -> bool {
+        self.redact && !self.redact_body_paths.is_empty()
+    }
+
+    fn redact_body(&self, json: &mut serde_json::Value) {
+        if self.redact {
+            for path in &self.redact_body_paths {
+                redact_path(json, path);
+            }
+        }
+    }
+
+    /// `body` as text, with its body paths redacted when it is JSON.
+    fn redact_text(&self, body: &[u8]) -> String {
+        if self.redacts_body() {
+            if let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(body) {
+                self.redact_body(&mut json);
+                return json.to_string();
+            }
+        }
+        String::from_utf8_lossy(body).to_string()
+    }
+}
+
+// Lists along the path are walked element by element, so `messages.content`
+// redacts the content of every message.
+fn redact_path(json: &mut serde_json::Value, path: &[String]) {
+    let Some((key, rest)) = path.split_first() else {
+        *json = serde_json::Value::String(REDACTED.to_string());
+        return;
+    };
+    match json {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                redact_path(item, path);
+            }
+        }
+        serde_json::Value::Object(object) => {
+            if let Some(value) = object.get_mut(key) {
+                redact_path(value, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[derive(Clone)]
+struct TrackedRequest {
+    collector: Arc<BamlCollector>,
+    stream_stats: Arc<StreamStats>,
+    tags: Arc<HashMap<String, String>>,
+}
+
+impl TrackedRequest {
+    fn has_tags(&self, tags: &HashMap<String, String>) -> bool {
+        tags.iter()
+            .all(|(key, value)| self.tags.get(key) == Some(value))
+    }
+}
+
+/// Chunk timing of a streamed request. BAML doesn't track it, so it is recorded
+/// as the stream events arrive. A request streams from one LLM call at a time, so
+/// the events are split between retries and fallbacks by the calls' start times.
+#[derive(Default)]
+pub struct StreamStats {
+    events: Mutex<Vec<StreamEvent>>,
+}
+
+struct StreamEvent {
+    utc_ms: i64,
+    has_content: bool,
+    parsed: bool,
+}
+
+/// The stream stats of a single LLM call.
+#[derive(Clone, Default)]
+pub struct CallStreamStats {
+    first_token_utc_ms: Option<i64>,
+    first_parsed_utc_ms: Option<i64>,
+    chunk_count: usize,
+}
+
+impl StreamStats {
+    pub fn record(&self, result: &FunctionResult) {
+        let utc_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        let has_content = matches!(
+            result.llm_response(),
+            LLMResponse::Success(response) if !response.content.is_empty()
+        );
+        self.events.lock().unwrap().push(StreamEvent {
+            utc_ms,
+            has_content,
+            parsed: matches!(result.parsed(), Some(Ok(_))),
+        });
+    }
+
+    // The first token is the first event carrying content, as providers may open
+    // the stream with empty chunks, e.g. one with only the assistant role
+    fn between(&self, start_utc_ms: i64, end_utc_ms: Option<i64>) -> CallStreamStats {
+        let events = self.events.lock().unwrap();
+        let mut stats = CallStreamStats::default();
+        for event in events.iter().filter(|event| {
+            event.utc_ms >= start_utc_ms && end_utc_ms.is_none_or(|end| event.utc_ms < end)
+        }) {
+            stats.chunk_count += 1;
+            if event.has_content {
+                stats.first_token_utc_ms.get_or_insert(event.utc_ms);
+            }
+            if event.parsed {
+                stats.first_parsed_utc_ms.get_or_insert(event.utc_ms);
+            }
+        }
+        stats
+    }
 }
 
 impl CollectorResource {
-    pub fn new(name: Option<String>) -> ResourceArc<CollectorResource> {
-        let collector = BamlCollector::new(name);
+    pub fn new(name: Option<String>, options: CollectorOptions) -> ResourceArc<CollectorResource> {
         ResourceArc::new(CollectorResource {
-            inner: Arc::new(collector),
+            name,
+            options: Arc::new(options),
+            requests: Mutex::new(VecDeque::new()),
         })
     }
 
+    /// Returns the BAML collector to attach to a new request. When the collector has a
+    /// `max_logs` limit, the oldest requests are forgotten to make room for it.
+    pub fn track(
+        &self,
+        stream_stats: Arc<StreamStats>,
+        tags: Arc<HashMap<String, String>>,
+    ) -> Arc<BamlCollector> {
+        let collector = Arc::new(BamlCollector::new(self.name.clone()));
+        let mut requests = self.requests.lock().unwrap();
+        requests.push_back(TrackedRequest {
+            collector: collector.clone(),
+            stream_stats,
+            tags,
+        });
+        if let Some(max_logs) = self.options.max_logs {
+            while requests.len() > max_logs {
+                requests.pop_front();
+            }
+        }
+        collector
+    }
+
+    fn requests(&self) -> Vec<TrackedRequest> {
+        self.requests.lock().unwrap().iter().cloned().collect()
+    }
+
     pub fn usage(&self) -> Usage {
-        Usage {
-            inner: self.inner.clone().usage(),
+        let mut total = baml_runtime::tracingv2::storage::storage::Usage::default();
+        let mut cost = (!self.options.prices.is_empty()).then_some(0.0);
+        for request in self.requests() {
+            let usage = request.collector.usage();
+            total.input_tokens = add_tokens(total.input_tokens, usage.input_tokens);
+            total.output_tokens = add_tokens(total.output_tokens, usage.output_tokens);
+            total.cached_input_tokens =
+                add_tokens(total.cached_input_tokens, usage.cached_input_tokens);
+            for mut log in request.collector.function_logs() {
+                let calls: Vec<_> = log.calls().iter().cloned().collect();
+                cost = cost
+                    .zip(self.options.calls_cost(&calls))
+                    .map(|(a, b)| a + b);
+            }
         }
+        Usage { inner: total, cost }
     }
 
     pub fn last_function_log(&self) -> Option<FunctionLog> {
-        self.inner.last_function_log().map(|log| FunctionLog {
-            inner: Arc::new(Mutex::new(log)),
+        self.requests().iter().rev().find_map(|request| {
+            request.collector.last_function_log().map(|log| {
+                FunctionLog::new(
+                    log,
+                    Some(request.stream_stats.clone()),
+                    request.tags.clone(),
+                    self.options.clone(),
+                )
+            })
+        })
+    }
+
+    /// Every function log recorded by the collector, oldest first. Only requests
+    /// tagged with all of `tags` are included.
+    pub fn function_logs(&self, tags: &HashMap<String, String>) -> Vec<FunctionLog> {
+        let requests = self
+            .requests()
+            .into_iter()
+            .filter(|request| request.has_tags(tags));
+        logs_of(requests, &self.options)
+    }
+
+    pub fn function_log_by_id(&self, id: &str) -> Option<FunctionLog> {
+        logs_of(self.requests(), &self.options)
+            .into_iter()
+            .find(|log| log.inner.lock().unwrap().id().to_string() == id)
+    }
+
+    /// Forgets every request and returns how many function logs were dropped.
+    pub fn clear(&self) -> usize {
+        let requests = std::mem::take(&mut *self.requests.lock().unwrap());
+        requests
+            .iter()
+            .map(|request| request.collector.function_logs().len())
+            .sum()
+    }
+
+    /// Forgets every request and returns their function logs, oldest first.
+    pub fn drain(&self) -> Vec<FunctionLog> {
+        let requests = std::mem::take(&mut *self.requests.lock().unwrap());
+        logs_of(requests, &self.options)
+    }
+}
+
+fn logs_of(
+    requests: impl IntoIterator<Item = TrackedRequest>,
+    options: &Arc<CollectorOptions>,
+) -> Vec<FunctionLog> {
+    requests
+        .into_iter()
+        .flat_map(|request| {
+            let stream_stats = request.stream_stats;
+            let tags = request.tags;
+            request
+                .collector
+                .function_logs()
+                .into_iter()
+                .map(move |log| {
+                    FunctionLog::new(
+                        log,
+                        Some(stream_stats.clone()),
+                        tags.clone(),
+                        options.clone(),
+                    )
+                })
         })
+        .collect()
+}
+
+fn add_tokens(total: Option<i64>, tokens: Option<i64>) -> Option<i64> {
+    match (total, tokens) {
+        (Some(total), Some(tokens)) => Some(total + tokens),
+        (total, tokens) => total.or(tokens),
     }
 }
 
 pub struct FunctionLog {
     pub inner: Arc<Mutex<baml_runtime::tracingv2::storage::storage::FunctionLog>>,
+    pub stream_stats: Option<Arc<StreamStats>>,
+    pub tags: Arc<HashMap<String, String>>,
+    pub options: Arc<CollectorOptions>,
+}
+
+impl FunctionLog {
+    pub fn new(
+        log: baml_runtime::tracingv2::storage::storage::FunctionLog,
+        stream_stats: Option<Arc<StreamStats>>,
+        tags: Arc<HashMap<String, String>>,
+        options: Arc<CollectorOptions>,
+    ) -> Self {
+        FunctionLog {
+            inner: Arc::new(Mutex::new(log)),
+            stream_stats,
+            tags,
+            options,
+        }
+    }
+
+    // Each call gets the stream events received from its start until the next call started
+    fn call(
+        &self,
+        calls: &[baml_runtime::tracingv2::storage::storage::LLMCallKind],
+        index: usize,
+    ) -> LLMCallKind {
+        let call = &calls[index];
+        let start_utc_ms = llm_call(call).timing.start_time_utc_ms;
+        let end_utc_ms = calls
+            .get(index + 1)
+            .map(|next| llm_call(next).timing.start_time_utc_ms);
+        LLMCallKind {
+            inner: call.clone(),
+            stream_stats: self
+                .stream_stats
+                .as_ref()
+                .map(|stats| stats.between(start_utc_ms, end_utc_ms)),
+            options: self.options.clone(),
+        }
+    }
+
+    /// Reasoning deltas streamed so far by the latest LLM call, read from its SSE events.
+    pub fn thinking_chunks(&self) -> Vec<String> {
+        let mut inner = self.inner.lock().unwrap();
+        let call_id = match inner.calls().last() {
+            Some(baml_runtime::tracingv2::storage::storage::LLMCallKind::Stream(stream)) => {
+                stream.llm_call.call_id.clone()
+            }
+            _ => return Vec::new(),
+        };
+        inner
+            .sse_chunks_for_call(&call_id)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|chunk| serde_json::from_str::<serde_json::Value>(&chunk.text).ok())
+            .filter_map(|event| thinking_delta(&event))
+            .collect()
+    }
+}
+
+fn thinking_delta(event: &serde_json::Value) -> Option<String> {
+    // Anthropic: {"type": "content_block_delta", "delta": {"type": "thinking_delta", "thinking": "..."}}
+    if let Some(thinking) = event.pointer("/delta/thinking").and_then(|t| t.as_str()) {
+        return Some(thinking.to_string());
+    }
+
+    // OpenAI compatible providers: {"choices": [{"delta": {"reasoning_content": "..."}}]}
+    if let Some(delta) = event.pointer("/choices/0/delta") {
+        for key in ["reasoning_content", "reasoning"] {
+            if let Some(reasoning) = delta.get(key).and_then(|r| r.as_str()) {
+                return Some(reasoning.to_string());
+            }
+        }
+    }
+
+    // Gemini: {"candidates": [{"content": {"parts": [{"text": "...", "thought": true}]}}]}
+    let parts = event.pointer("/candidates/0/content/parts")?.as_array()?;
+    let thoughts: String = parts
+        .iter()
+        .filter(|part| part.get("thought").and_then(|t| t.as_bool()) == Some(true))
+        .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+        .collect();
+    (!thoughts.is_empty()).then_some(thoughts)
 }
 
 pub struct Usage {
     pub inner: baml_runtime::tracingv2::storage::storage::Usage,
+    /// In USD, computed from the collector's prices.
+    pub cost: Option<f64>,
 }
 
 pub struct Timing {
@@ -44,18 +603,24 @@ pub struct Timing {
 
 pub struct StreamTiming {
     pub inner: baml_runtime::tracingv2::storage::storage::StreamTiming,
+    pub stream_stats: Option<CallStreamStats>,
 }
 
 pub struct LLMCallKind {
     pub inner: baml_runtime::tracingv2::storage::storage::LLMCallKind,
+    pub stream_stats: Option<CallStreamStats>,
+    pub options: Arc<CollectorOptions>,
 }
 
 pub struct LLMCall {
     pub inner: baml_runtime::tracingv2::storage::storage::LLMCall,
+    pub options: Arc<CollectorOptions>,
 }
 
 pub struct LLMStreamCall {
     pub inner: baml_runtime::tracingv2::storage::storage::LLMStreamCall,
+    pub stream_stats: Option<CallStreamStats>,
+    pub options: Arc<CollectorOptions>,
 }
 
 impl Encoder for Usage {
@@ -67,6 +632,8 @@ impl Encoder for Usage {
             .unwrap()
             .map_put("cached_input_tokens", self.inner.cached_input_tokens)
             .unwrap()
+            .map_put("cost", self.cost)
+            .unwrap()
     }
 }
 
@@ -89,19 +656,28 @@ impl Encoder for Timing {
 impl Encoder for StreamTiming {
     fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
         let map = Term::map_new(env);
-        map.map_put("start_time_utc_ms", self.inner.start_time_utc_ms)
+        let start_time_utc_ms = self.inner.start_time_utc_ms;
+        let stats = self.stream_stats.as_ref();
+        map.map_put("start_time_utc_ms", start_time_utc_ms)
             .unwrap()
             .map_put("duration_ms", self.inner.duration_ms)
             .unwrap()
-        // TODO: BAML doesn't track this yet
-        // .map_put(
-        //     "time_to_first_parsed_ms",
-        //     self.inner.time_to_first_parsed_ms,
-        // )
-        // .unwrap()
-        // TODO: BAML doesn't track this yet
-        // .map_put("time_to_first_token_ms", self.inner.time_to_first_token_ms)
-        // .unwrap()
+            .map_put(
+                "time_to_first_token_ms",
+                stats
+                    .and_then(|s| s.first_token_utc_ms)
+                    .map(|t| t - start_time_utc_ms),
+            )
+            .unwrap()
+            .map_put(
+                "time_to_first_parsed_ms",
+                stats
+                    .and_then(|s| s.first_parsed_utc_ms)
+                    .map(|t| t - start_time_utc_ms),
+            )
+            .unwrap()
+            .map_put("chunk_count", stats.map(|s| s.chunk_count))
+            .unwrap()
     }
 }
 
@@ -122,14 +698,12 @@ impl Encoder for LLMCall {
             .map_put(
                 "request",
                 self.inner.request.as_deref().map(|r| {
-                    let map = Term::map_new(env);
+                    let map = put_body(env, Term::map_new(env), r.body.raw(), &self.options);
                     map.map_put("method", r.method.clone())
                         .unwrap()
-                        .map_put("url", r.url.clone())
+                        .map_put("url", self.options.redact_url(&r.url))
                         .unwrap()
-                        .map_put("headers", r.headers().clone())
-                        .unwrap()
-                        .map_put("body", r.body.text().unwrap_or_default().encode(env))
+                        .map_put("headers", self.options.redact_headers(r.headers().iter()))
                         .unwrap()
                 }),
             )
@@ -137,12 +711,10 @@ impl Encoder for LLMCall {
             .map_put(
                 "response",
                 self.inner.response.as_deref().map(|r| {
-                    let map = Term::map_new(env);
+                    let map = put_body(env, Term::map_new(env), r.body.raw(), &self.options);
                     map.map_put("status", r.status.clone())
                         .unwrap()
-                        .map_put("headers", r.headers())
-                        .unwrap()
-                        .map_put("body", r.body.text().unwrap_or_default().encode(env))
+                        .map_put("headers", self.options.redact_headers(r.headers().iter()))
                         .unwrap()
                 }),
             )
@@ -151,9 +723,16 @@ impl Encoder for LLMCall {
                 "usage",
                 Usage {
                     inner: self.inner.usage.clone().unwrap_or_default(),
+                    cost: self.options.cost(&self.inner),
                 },
             )
             .unwrap()
+            .map_put("selected", self.inner.selected)
+            .unwrap()
+            .map_put("finish_reason", finish_reason(&self.inner))
+            .unwrap()
+            .map_put("error", call_error(&self.inner, &self.options))
+            .unwrap()
     }
 }
 
@@ -168,20 +747,19 @@ impl Encoder for LLMStreamCall {
                 "timing",
                 StreamTiming {
                     inner: self.inner.timing.clone(),
+                    stream_stats: self.stream_stats.clone(),
                 },
             )
             .unwrap()
             .map_put(
                 "request",
                 self.inner.llm_call.request.as_deref().map(|r| {
-                    let map = Term::map_new(env);
+                    let map = put_body(env, Term::map_new(env), r.body.raw(), &self.options);
                     map.map_put("method", r.method.clone())
                         .unwrap()
-                        .map_put("url", r.url.clone())
-                        .unwrap()
-                        .map_put("headers", r.headers())
+                        .map_put("url", self.options.redact_url(&r.url))
                         .unwrap()
-                        .map_put("body", r.body.text().unwrap_or_default().encode(env))
+                        .map_put("headers", self.options.redact_headers(r.headers().iter()))
                         .unwrap()
                 }),
             )
@@ -189,12 +767,10 @@ impl Encoder for LLMStreamCall {
             .map_put(
                 "response",
                 self.inner.llm_call.response.as_deref().map(|r| {
-                    let map = Term::map_new(env);
+                    let map = put_body(env, Term::map_new(env), r.body.raw(), &self.options);
                     map.map_put("status", r.status.clone())
                         .unwrap()
-                        .map_put("headers", r.headers())
-                        .unwrap()
-                        .map_put("body", r.body.text().unwrap_or_default().encode(env))
+                        .map_put("headers", self.options.redact_headers(r.headers().iter()))
                         .unwrap()
                 }),
             )
@@ -203,9 +779,16 @@ impl Encoder for LLMStreamCall {
                 "usage",
                 Usage {
                     inner: self.inner.llm_call.usage.clone().unwrap_or_default(),
+                    cost: self.options.cost(&self.inner.llm_call),
                 },
             )
             .unwrap()
+            .map_put("selected", self.inner.llm_call.selected)
+            .unwrap()
+            .map_put("finish_reason", finish_reason(&self.inner.llm_call))
+            .unwrap()
+            .map_put("error", call_error(&self.inner.llm_call, &self.options))
+            .unwrap()
     }
 }
 
@@ -214,11 +797,14 @@ impl Encoder for LLMCallKind {
         match &self.inner {
             baml_runtime::tracingv2::storage::storage::LLMCallKind::Basic(call) => LLMCall {
                 inner: call.clone(),
+                options: self.options.clone(),
             }
             .encode(env),
             baml_runtime::tracingv2::storage::storage::LLMCallKind::Stream(stream) => {
                 LLMStreamCall {
                     inner: stream.clone(),
+                    stream_stats: self.stream_stats.clone(),
+                    options: self.options.clone(),
                 }
                 .encode(env)
             }
@@ -230,6 +816,7 @@ impl Encoder for FunctionLog {
     fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
         let map = Term::map_new(env);
         let mut inner = self.inner.lock().unwrap();
+        let calls: Vec<_> = inner.calls().iter().cloned().collect();
         map.map_put("id", inner.id().to_string())
             .unwrap()
             .map_put("function_name", inner.function_name())
@@ -247,18 +834,29 @@ impl Encoder for FunctionLog {
                 "usage",
                 Usage {
                     inner: inner.usage().clone(),
+                    cost: self.options.calls_cost(&calls),
                 },
             )
             .unwrap()
             .map_put(
                 "calls",
-                inner
-                    .calls()
-                    .iter()
-                    .map(|c| LLMCallKind { inner: c.clone() }.encode(env))
+                (0..calls.len())
+                    .map(|index| self.call(&calls, index).encode(env))
                     .collect::<Vec<_>>(),
             )
             .unwrap()
+            .map_put(
+                "selected_call",
+                calls
+                    .iter()
+                    .position(|c| llm_call(c).selected)
+                    .map(|index| self.call(&calls, index)),
+            )
+            .unwrap()
+            .map_put("inferred_strategy", inferred_strategy(env, &calls))
+            .unwrap()
+            .map_put("tags", self.tags.as_ref())
+            .unwrap()
             .map_put(
                 "raw_llm_response",
                 inner.raw_llm_response().unwrap_or_default().encode(env),
@@ -266,3 +864,190 @@ impl Encoder for FunctionLog {
             .unwrap()
     }
 }
+
+fn llm_call(
+    call: &baml_runtime::tracingv2::storage::storage::LLMCallKind,
+) -> &baml_runtime::tracingv2::storage::storage::LLMCall {
+    match call {
+        baml_runtime::tracingv2::storage::storage::LLMCallKind::Basic(call) => call,
+        baml_runtime::tracingv2::storage::storage::LLMCallKind::Stream(stream) => &stream.llm_call,
+    }
+}
+
+// Guesses how the calls of a function log came about: a single call, retries of the
+// same client, or fallbacks to other clients. Function logs don't record the client
+// strategy, so a call to a client that was called before counts as a retry and a call
+// to any other client as a fallback. A fallback list naming the same client twice is
+// therefore reported as retries.
+fn inferred_strategy<'a>(
+    env: Env<'a>,
+    calls: &[baml_runtime::tracingv2::storage::storage::LLMCallKind],
+) -> Term<'a> {
+    let clients: Vec<String> = calls
+        .iter()
+        .map(|c| llm_call(c).client_name.clone())
+        .collect();
+    let mut retries = 0;
+    let mut fallbacks = 0;
+    for (index, client) in clients.iter().enumerate().skip(1) {
+        if clients[..index].contains(client) {
+            retries += 1;
+        } else {
+            fallbacks += 1;
+        }
+    }
+    let strategy_type = if fallbacks > 0 {
+        "fallback"
+    } else if retries > 0 {
+        "retry"
+    } else {
+        "single"
+    };
+
+    Term::map_new(env)
+        .map_put("type", strategy_type)
+        .unwrap()
+        .map_put("clients", clients)
+        .unwrap()
+        .map_put("retries", retries)
+        .unwrap()
+        .map_put("fallbacks", fallbacks)
+        .unwrap()
+}
+
+// Reads the model from the request body, falling back to the model in the URL for
+// providers such as Google AI, Vertex and Bedrock.
+fn model_of(call: &baml_runtime::tracingv2::storage::storage::LLMCall) -> Option<String> {
+    let request = call.request.as_deref()?;
+    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(request.body.raw()) {
+        if let Some(model) = json.get("model").and_then(|m| m.as_str()) {
+            return Some(model.to_string());
+        }
+    }
+    let path = request.url.split('?').next()?;
+    if let Some((_, model)) = path.rsplit_once("/models/") {
+        return model.split(':').next().map(|m| m.to_string());
+    }
+    // Bedrock model ids contain colons, e.g. `anthropic.claude-3-haiku-20240307-v1:0`
+    let (_, model) = path.rsplit_once("/model/")?;
+    let model = model.split('/').next()?;
+    Some(model.replace("%3A", ":").replace("%3a", ":"))
+}
+
+// Reads the finish reason reported by the provider from the response body. Streamed
+// responses report it in their last SSE events.
+fn finish_reason(call: &baml_runtime::tracingv2::storage::storage::LLMCall) -> Option<String> {
+    let body = call.response.as_deref()?.body.text().ok()?;
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body) {
+        return finish_reason_of(&json);
+    }
+    body.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str::<serde_json::Value>(data.trim()).ok())
+        .filter_map(|event| finish_reason_of(&event))
+        .last()
+}
+
+fn finish_reason_of(json: &serde_json::Value) -> Option<String> {
+    [
+        "/choices/0/finish_reason",
+        "/stop_reason",
+        "/delta/stop_reason",
+        "/candidates/0/finishReason",
+        "/output/0/status",
+    ]
+    .iter()
+    .find_map(|pointer| json.pointer(pointer).and_then(|r| r.as_str()))
+    .map(|reason| reason.to_string())
+}
+
+fn call_error(
+    call: &baml_runtime::tracingv2::storage::storage::LLMCall,
+    options: &CollectorOptions,
+) -> Option<String> {
+    match call.response.as_deref() {
+        None => (!call.selected).then(|| "No response received".to_string()),
+        Some(response) if !(200..300).contains(&response.status) => Some(format!(
+            "HTTP {}: {}",
+            response.status,
+            options.redact_text(response.body.raw())
+        )),
+        Some(_) => None,
+    }
+}
+
+// Puts `body`, `body_size` and `body_truncated` into a request or response map.
+fn put_body<'a>(env: Env<'a>, map: Term<'a>, bytes: &[u8], options: &CollectorOptions) -> Term<'a> {
+    let max_body_bytes = options.max_body_bytes.unwrap_or(usize::MAX);
+    let truncated = bytes.len() > max_body_bytes;
+    // Body paths are redacted in the whole body, as a cut JSON body no longer parses. Raw
+    // bodies can't be combined with body paths, see `BamlElixir.Collector.new/2`.
+    let body_term = match options.body {
+        BodyMode::Json if !truncated => match serde_json::from_slice::<serde_json::Value>(bytes) {
+            Ok(mut json) => {
+                options.redact_body(&mut json);
+                json_to_term(env, &json)
+            }
+            Err(_) => String::from_utf8_lossy(bytes).encode(env),
+        },
+        BodyMode::Json | BodyMode::Text => {
+            let text = options.redact_text(bytes);
+            if !truncated {
+                text.encode(env)
+            } else if options.redacts_body()
+                && serde_json::from_slice::<serde_json::Value>(bytes).is_err()
+            {
+                // A body that can't be redacted is left out rather than cut
+                REDACTED.encode(env)
+            } else {
+                truncate_str(&text, max_body_bytes).encode(env)
+            }
+        }
+        BodyMode::Raw => {
+            let body = &bytes[..bytes.len().min(max_body_bytes)];
+            let mut binary = OwnedBinary::new(body.len()).unwrap();
+            binary.as_mut_slice().copy_from_slice(body);
+            binary.release(env).encode(env)
+        }
+    };
+
+    map.map_put("body", body_term)
+        .unwrap()
+        .map_put("body_size", bytes.len())
+        .unwrap()
+        .map_put("body_truncated", truncated)
+        .unwrap()
+}
+
+// Cuts `text` to at most `max_bytes`, without splitting a character.
+fn truncate_str(text: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn json_to_term<'a>(env: Env<'a>, json: &serde_json::Value) -> Term<'a> {
+    match json {
+        serde_json::Value::Null => rustler::types::atom::nil().encode(env),
+        serde_json::Value::Bool(b) => b.encode(env),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => i.encode(env),
+            None => n.as_f64().unwrap_or_default().encode(env),
+        },
+        serde_json::Value::String(s) => s.encode(env),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| json_to_term(env, item))
+            .collect::<Vec<_>>()
+            .encode(env),
+        serde_json::Value::Object(object) => {
+            let mut map = Term::map_new(env);
+            for (key, value) in object {
+                map = map.map_put(key, json_to_term(env, value)).unwrap();
+            }
+            map
+        }
+    }
+}
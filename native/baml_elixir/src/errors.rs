@@ -0,0 +1,392 @@
+use baml_runtime::errors::ExposedError;
+use baml_runtime::internal::llm_client::LLMResponse;
+use baml_runtime::FunctionResult;
+use jsonish::deserializer::coercer::ParsingError;
+use rustler::{Encoder, Env, Error, Term};
+
+mod atoms {
+    rustler::atoms! {
+        error_type = "type",
+        message,
+        detailed_message,
+        raw_output,
+        prompt,
+        client_name,
+        status_code,
+        finish_reason,
+        model,
+        diagnostics,
+        validation_error,
+        finish_reason_error,
+        client_http_error,
+        timeout_error,
+        cancelled,
+        invalid_argument,
+        configuration_error,
+        type_builder_error,
+        internal_error,
+        severity,
+        line,
+        error,
+        warning,
+    }
+}
+
+/// Errors returned to Elixir as `{:error, %{type: ..., message: ...}}`.
+pub enum BamlError {
+    Validation {
+        message: String,
+        raw_output: String,
+        prompt: String,
+        detailed_message: String,
+        client_name: Option<String>,
+        model: Option<String>,
+        diagnostics: Option<Vec<Diagnostic>>,
+    },
+    FinishReason {
+        message: String,
+        raw_output: String,
+        prompt: String,
+        finish_reason: Option<String>,
+        detailed_message: String,
+    },
+    ClientHttp {
+        message: String,
+        client_name: String,
+        status_code: u16,
+        detailed_message: String,
+    },
+    Timeout {
+        message: String,
+        client_name: String,
+    },
+    Cancelled {
+        message: String,
+    },
+    InvalidArgument {
+        message: Box<dyn Encoder>,
+    },
+    Configuration {
+        message: String,
+    },
+    TypeBuilder {
+        message: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Internal {
+        message: String,
+        detailed_message: String,
+    },
+}
+
+impl BamlError {
+    pub fn from_anyhow(err: &anyhow::Error) -> Self {
+        match err.downcast_ref::<ExposedError>() {
+            Some(ExposedError::ValidationError {
+                prompt,
+                raw_output,
+                message,
+                detailed_message,
+            }) => BamlError::Validation {
+                message: message.clone(),
+                raw_output: raw_output.clone(),
+                prompt: prompt.clone(),
+                detailed_message: detailed_message.clone(),
+                client_name: None,
+                model: None,
+                diagnostics: None,
+            },
+            Some(ExposedError::FinishReasonError {
+                prompt,
+                raw_output,
+                message,
+                finish_reason,
+                detailed_message,
+            }) => BamlError::FinishReason {
+                message: message.clone(),
+                raw_output: raw_output.clone(),
+                prompt: prompt.clone(),
+                finish_reason: finish_reason.clone(),
+                detailed_message: detailed_message.clone(),
+            },
+            Some(ExposedError::ClientHttpError {
+                client_name,
+                message,
+                status_code,
+                detailed_message,
+            }) => BamlError::ClientHttp {
+                message: message.clone(),
+                client_name: client_name.clone(),
+                status_code: status_code.to_u16(),
+                detailed_message: detailed_message.clone(),
+            },
+            Some(ExposedError::TimeoutError {
+                client_name,
+                message,
+            }) => BamlError::Timeout {
+                message: message.clone(),
+                client_name: client_name.clone(),
+            },
+            Some(ExposedError::AbortError { detailed_message }) => BamlError::Cancelled {
+                message: detailed_message.clone(),
+            },
+            None => BamlError::Internal {
+                message: err.to_string(),
+                detailed_message: format!("{:?}", err),
+            },
+        }
+    }
+
+    /// Like `from_anyhow`, but fills parse failures in with the client, model, raw
+    /// LLM output and parser diagnostics held by the function result.
+    pub fn from_function_result(result: &FunctionResult, err: &anyhow::Error) -> Self {
+        let mut error = BamlError::from_anyhow(err);
+        if let BamlError::Validation {
+            raw_output,
+            prompt,
+            client_name,
+            model,
+            diagnostics,
+            ..
+        } = &mut error
+        {
+            if let LLMResponse::Success(response) = result.llm_response() {
+                *client_name = Some(response.client.clone());
+                *model = Some(response.model.clone());
+                if raw_output.is_empty() {
+                    *raw_output = response.content.clone();
+                }
+                if prompt.is_empty() {
+                    *prompt = response.prompt.to_string();
+                }
+            }
+            if let Some(Err(parse_error)) = result.parsed() {
+                *diagnostics = Some(match parse_error.downcast_ref::<ParsingError>() {
+                    Some(parsing_error) => parsing_diagnostics(parsing_error),
+                    None => parse_error
+                        .chain()
+                        .map(|cause| Diagnostic::new(true, &cause.to_string(), None))
+                        .collect(),
+                });
+            }
+        }
+        error
+    }
+
+    pub fn configuration(message: impl Into<String>) -> Self {
+        BamlError::Configuration {
+            message: message.into(),
+        }
+    }
+
+    /// BAML added through the type builder failed to compile. Without `diagnostics`, the
+    /// error message is reported as the only diagnostic.
+    pub fn type_builder(err: &anyhow::Error, mut diagnostics: Vec<Diagnostic>) -> Self {
+        let message = strip_ansi(&err.to_string());
+        if diagnostics.is_empty() {
+            diagnostics.push(Diagnostic::new(true, &message, None));
+        }
+        BamlError::TypeBuilder {
+            message,
+            diagnostics,
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        let message = message.into();
+        BamlError::Internal {
+            detailed_message: message.clone(),
+            message,
+        }
+    }
+}
+
+// One diagnostic per cause the parser gave up on, e.g. `person.age: Expected a number`.
+fn parsing_diagnostics(error: &ParsingError) -> Vec<Diagnostic> {
+    if error.causes.is_empty() {
+        let scope = if error.scope.is_empty() {
+            "<root>".to_string()
+        } else {
+            error.scope.join(".")
+        };
+        return vec![Diagnostic::new(
+            true,
+            &format!("{}: {}", scope, error.reason),
+            None,
+        )];
+    }
+    error.causes.iter().flat_map(parsing_diagnostics).collect()
+}
+
+// Removes the colors of pretty-printed diagnostics, e.g. `\x1b[1;91merror\x1b[0m`.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// A diagnostic reported when compiling BAML or parsing LLM output, encoded as
+/// `%{severity: :error | :warning, message: ..., line: ...}`.
+pub struct Diagnostic {
+    is_error: bool,
+    message: String,
+    line: Option<usize>,
+}
+
+impl Diagnostic {
+    pub fn new(is_error: bool, message: &str, line: Option<usize>) -> Self {
+        Diagnostic {
+            is_error,
+            message: message.to_string(),
+            line,
+        }
+    }
+}
+
+impl Encoder for Diagnostic {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let severity = if self.is_error {
+            atoms::error()
+        } else {
+            atoms::warning()
+        };
+        Term::map_new(env)
+            .map_put(atoms::severity(), severity)
+            .unwrap()
+            .map_put(atoms::message(), &self.message)
+            .unwrap()
+            .map_put(atoms::line(), self.line)
+            .unwrap()
+    }
+}
+
+/// Wraps errors raised while decoding NIF arguments as `:invalid_argument` errors.
+pub fn invalid_argument(err: Error) -> Error {
+    match err {
+        Error::Term(message) => Error::Term(Box::new(BamlError::InvalidArgument { message })),
+        Error::BadArg => Error::Term(Box::new(BamlError::InvalidArgument {
+            message: Box::new("Invalid argument"),
+        })),
+        other => other,
+    }
+}
+
+impl Encoder for BamlError {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let map = Term::map_new(env);
+        match self {
+            BamlError::Validation {
+                message,
+                raw_output,
+                prompt,
+                detailed_message,
+                client_name,
+                model,
+                diagnostics,
+            } => map
+                .map_put(atoms::error_type(), atoms::validation_error())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap()
+                .map_put(atoms::raw_output(), raw_output)
+                .unwrap()
+                .map_put(atoms::prompt(), prompt)
+                .unwrap()
+                .map_put(atoms::detailed_message(), detailed_message)
+                .unwrap()
+                .map_put(atoms::client_name(), client_name)
+                .unwrap()
+                .map_put(atoms::model(), model)
+                .unwrap()
+                .map_put(atoms::diagnostics(), diagnostics)
+                .unwrap(),
+            BamlError::FinishReason {
+                message,
+                raw_output,
+                prompt,
+                finish_reason,
+                detailed_message,
+            } => map
+                .map_put(atoms::error_type(), atoms::finish_reason_error())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap()
+                .map_put(atoms::raw_output(), raw_output)
+                .unwrap()
+                .map_put(atoms::prompt(), prompt)
+                .unwrap()
+                .map_put(atoms::finish_reason(), finish_reason)
+                .unwrap()
+                .map_put(atoms::detailed_message(), detailed_message)
+                .unwrap(),
+            BamlError::ClientHttp {
+                message,
+                client_name,
+                status_code,
+                detailed_message,
+            } => map
+                .map_put(atoms::error_type(), atoms::client_http_error())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap()
+                .map_put(atoms::client_name(), client_name)
+                .unwrap()
+                .map_put(atoms::status_code(), status_code)
+                .unwrap()
+                .map_put(atoms::detailed_message(), detailed_message)
+                .unwrap(),
+            BamlError::Timeout {
+                message,
+                client_name,
+            } => map
+                .map_put(atoms::error_type(), atoms::timeout_error())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap()
+                .map_put(atoms::client_name(), client_name)
+                .unwrap(),
+            BamlError::Cancelled { message } => map
+                .map_put(atoms::error_type(), atoms::cancelled())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap(),
+            BamlError::InvalidArgument { message } => map
+                .map_put(atoms::error_type(), atoms::invalid_argument())
+                .unwrap()
+                .map_put(atoms::message(), message.as_ref().encode(env))
+                .unwrap(),
+            BamlError::Configuration { message } => map
+                .map_put(atoms::error_type(), atoms::configuration_error())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap(),
+            BamlError::TypeBuilder {
+                message,
+                diagnostics,
+            } => map
+                .map_put(atoms::error_type(), atoms::type_builder_error())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap()
+                .map_put(atoms::diagnostics(), diagnostics)
+                .unwrap(),
+            BamlError::Internal {
+                message,
+                detailed_message,
+            } => map
+                .map_put(atoms::error_type(), atoms::internal_error())
+                .unwrap()
+                .map_put(atoms::message(), message)
+                .unwrap()
+                .map_put(atoms::detailed_message(), detailed_message)
+                .unwrap(),
+        }
+    }
+}
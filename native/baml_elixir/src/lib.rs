@@ -1,17 +1,20 @@
 use baml_runtime::client_registry::{ClientProperty, ClientProvider, ClientRegistry};
 use baml_runtime::tracingv2::storage::storage::Collector;
 use baml_runtime::type_builder::TypeBuilder;
-use baml_runtime::{BamlRuntime, FunctionResult, RuntimeContextManager, TripWire};
+use baml_runtime::{FunctionResult, FunctionResultStream, RuntimeContextManager, TripWire};
 use baml_types::ir_type::UnionTypeViewGeneric;
-use baml_types::{BamlMap, BamlValue, LiteralValue, TypeIR};
+use baml_types::{
+    BamlMap, BamlValue, BamlValueWithMeta, CompletionState, LiteralValue, ResponseCheck, TypeIR,
+};
+use jsonish::ResponseValueMeta;
 use rustler::types::atom;
 use stream_cancel::Trigger;
 
 use collector::{FunctionLog, Usage};
+use errors::BamlError;
 use rustler::{Encoder, Env, Error, LocalPid, MapIterator, NifResult, Resource, ResourceArc, Term};
 use std::collections::HashMap;
 use std::panic::AssertUnwindSafe;
-use std::path::Path;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 mod atoms {
@@ -19,10 +22,25 @@ mod atoms {
         partial,
         done,
         ok,
+        value,
+        checks,
+        name,
+        expression,
+        status,
+        state,
+        pending,
+        incomplete,
+        complete,
+        trace_id,
     }
 }
 
+mod async_runtime;
 mod collector;
+mod errors;
+mod media;
+mod runtime;
+mod tick;
 mod type_builder;
 
 #[rustler::resource_impl()]
@@ -67,6 +85,10 @@ fn term_to_baml_value<'a>(term: Term<'a>) -> Result<BamlValue, Error> {
         return Ok(BamlValue::List(baml_list));
     }
 
+    if media::is_media_term(term) {
+        return Ok(BamlValue::Media(media::term_to_media(term)?));
+    }
+
     if term.is_map() {
         let mut map = BamlMap::new();
         for (key_term, value_term) in
@@ -168,31 +190,108 @@ fn term_to_client_property(
     Ok(ClientProperty::new(name, provider, retry_policy, options))
 }
 
-fn baml_value_to_term<'a>(env: Env<'a>, value: &BamlValue) -> NifResult<Term<'a>> {
-    match value {
-        BamlValue::String(s) => Ok(s.encode(env)),
-        BamlValue::Int(i) => Ok(i.encode(env)),
-        BamlValue::Float(f) => Ok(f.encode(env)),
-        BamlValue::Bool(b) => Ok(b.encode(env)),
-        BamlValue::Null => Ok(atom::nil().encode(env)),
-        BamlValue::List(items) => {
+/// Which values of a result are wrapped with their completion state.
+#[derive(Clone, Copy)]
+enum StateEncoding {
+    /// Final results are always complete, so no state is attached.
+    Omit,
+    /// Partial results only carry state for `@stream.with_state` fields.
+    Annotated,
+    /// Every value of a partial result carries its state (`stream_state: true`).
+    All,
+}
+
+/// Options passed from Elixir as a map alongside each request.
+#[derive(Default)]
+struct CallOptions {
+    stream_state: bool,
+    tick: bool,
+    tags: HashMap<String, String>,
+    meta: bool,
+}
+
+impl CallOptions {
+    fn tags(&self) -> Option<&HashMap<String, String>> {
+        if self.tags.is_empty() {
+            None
+        } else {
+            Some(&self.tags)
+        }
+    }
+
+    fn partial_state(&self) -> StateEncoding {
+        if self.stream_state {
+            StateEncoding::All
+        } else {
+            StateEncoding::Annotated
+        }
+    }
+}
+
+fn term_to_call_options(term: Term) -> Result<CallOptions, Error> {
+    let mut options = CallOptions::default();
+    if term.is_atom() && term.decode::<rustler::Atom>()? == atom::nil() {
+        return Ok(options);
+    }
+    let iter = MapIterator::new(term).ok_or(Error::Term(Box::new("Options must be a map")))?;
+    for (key_term, value_term) in iter {
+        let key = term_to_string(key_term)?;
+        match key.as_str() {
+            "stream_state" => options.stream_state = value_term.decode()?,
+            "tick" => options.tick = value_term.decode()?,
+            "tags" => options.tags = term_to_tags(value_term)?,
+            "meta" => options.meta = value_term.decode()?,
+            _ => {}
+        }
+    }
+    Ok(options)
+}
+
+fn term_to_tags(term: Term) -> Result<HashMap<String, String>, Error> {
+    let mut tags = HashMap::new();
+    if term.is_atom() && term.decode::<rustler::Atom>()? == atom::nil() {
+        return Ok(tags);
+    }
+    let iter = MapIterator::new(term).ok_or(Error::Term(Box::new("Tags must be a map")))?;
+    for (key_term, value_term) in iter {
+        let key = term_to_string(key_term)?;
+        let value = term_to_string(value_term).map_err(|_| {
+            Error::Term(Box::new(format!("Tag {} must be a string or an atom", key)))
+        })?;
+        tags.insert(key, value);
+    }
+    Ok(tags)
+}
+
+fn baml_value_to_term<'a>(
+    env: Env<'a>,
+    value: &BamlValueWithMeta<ResponseValueMeta>,
+    state: StateEncoding,
+) -> NifResult<Term<'a>> {
+    let term = match value {
+        BamlValueWithMeta::String(s, _) => Ok(s.encode(env)),
+        BamlValueWithMeta::Int(i, _) => Ok(i.encode(env)),
+        BamlValueWithMeta::Float(f, _) => Ok(f.encode(env)),
+        BamlValueWithMeta::Bool(b, _) => Ok(b.encode(env)),
+        BamlValueWithMeta::Null(_) => Ok(atom::nil().encode(env)),
+        BamlValueWithMeta::List(items, _) => {
             let terms: Result<Vec<Term>, Error> = items
                 .iter()
-                .map(|item| baml_value_to_term(env, item))
+                .map(|item| baml_value_to_term(env, item, state))
                 .collect();
             Ok(terms?.encode(env))
         }
-        BamlValue::Map(map) => {
+        BamlValueWithMeta::Map(map, _) => {
             let mut result_map = Term::map_new(env);
             for (key, value) in map.iter() {
-                let value_term = baml_value_to_term(env, value)?;
+                let value_term = baml_value_to_term(env, value, state)?;
                 result_map = result_map
                     .map_put(key.encode(env), value_term)
                     .map_err(|_| Error::Term(Box::new("Failed to add key to map")))?;
             }
             Ok(result_map)
         }
-        BamlValue::Class(class_name, map) => {
+        BamlValueWithMeta::Class(class_name, map, _) => {
             let mut result_map = Term::map_new(env);
             let class_atom = rustler::Atom::from_str(env, "__baml_class__")
                 .map_err(|_| Error::Term(Box::new("Failed to create atom")))?;
@@ -202,18 +301,15 @@ fn baml_value_to_term<'a>(env: Env<'a>, value: &BamlValue) -> NifResult<Term<'a>
             for (key, value) in map.iter() {
                 let key_atom = rustler::Atom::from_str(env, key)
                     .map_err(|_| Error::Term(Box::new("Failed to create key atom")))?;
-                let value_term = baml_value_to_term(env, value)?;
+                let value_term = baml_value_to_term(env, value, state)?;
                 result_map = result_map
                     .map_put(key_atom.encode(env), value_term)
                     .map_err(|_| Error::Term(Box::new("Failed to add key to map")))?;
             }
             Ok(result_map)
         }
-        BamlValue::Media(_media) => {
-            // For now, return an error since we need to check the actual BamlMedia structure
-            Err(Error::Term(Box::new("Media type not yet supported")))
-        }
-        BamlValue::Enum(enum_type, variant) => {
+        BamlValueWithMeta::Media(media, _) => media::media_to_term(env, media),
+        BamlValueWithMeta::Enum(enum_type, variant, _) => {
             // Convert enum to a map with __baml_enum__ and value
             let mut result_map = Term::map_new(env);
             let enum_atom = rustler::Atom::from_str(env, "__baml_enum__")
@@ -228,36 +324,120 @@ fn baml_value_to_term<'a>(env: Env<'a>, value: &BamlValue) -> NifResult<Term<'a>
                 .map_err(|_| Error::Term(Box::new("Failed to add enum variant")))?;
             Ok(result_map)
         }
+    }?;
+
+    wrap_value(env, term, value.meta(), state)
+}
+
+// Values with `@check` constraints are wrapped as `%{value: ..., checks: %{name => check}}`
+// so that failed checks reach the caller instead of being dropped. Partial values also get
+// `state: :pending | :incomplete | :complete` for `@stream.with_state` fields or when requested.
+fn wrap_value<'a>(
+    env: Env<'a>,
+    term: Term<'a>,
+    meta: &ResponseValueMeta,
+    state: StateEncoding,
+) -> NifResult<Term<'a>> {
+    let checks = &meta.1;
+    let with_state = match state {
+        StateEncoding::Omit => false,
+        StateEncoding::Annotated => meta.3.meta().streaming_behavior.state,
+        StateEncoding::All => true,
+    };
+    if checks.is_empty() && !with_state {
+        return Ok(term);
+    }
+
+    let mut wrapped = Term::map_new(env).map_put(atoms::value(), term)?;
+    if !checks.is_empty() {
+        wrapped = wrapped.map_put(atoms::checks(), checks_to_term(env, checks)?)?;
+    }
+    if with_state {
+        wrapped = wrapped.map_put(atoms::state(), completion_state_to_atom(&meta.2.state))?;
     }
+    Ok(wrapped)
+}
+
+fn completion_state_to_atom(state: &CompletionState) -> rustler::Atom {
+    match state {
+        CompletionState::Pending => atoms::pending(),
+        CompletionState::Incomplete => atoms::incomplete(),
+        CompletionState::Complete => atoms::complete(),
+    }
+}
+
+fn checks_to_term<'a>(env: Env<'a>, checks: &[ResponseCheck]) -> NifResult<Term<'a>> {
+    let mut checks_map = Term::map_new(env);
+    for check in checks {
+        let check_map = Term::map_new(env)
+            .map_put(atoms::name(), check.name.clone())?
+            .map_put(atoms::expression(), check.expression.clone())?
+            .map_put(atoms::status(), check.status.clone())?;
+        checks_map = checks_map.map_put(check.name.encode(env), check_map)?;
+    }
+    Ok(checks_map)
 }
 
 fn prepare_request<'a>(
     env: Env<'a>,
     args: Term<'a>,
-    path: String,
+    runtime: Term<'a>,
     collectors: Vec<ResourceArc<collector::CollectorResource>>,
     client_registry: Term<'a>,
     tb_elixir: Term<'a>,
+    options: &CallOptions,
 ) -> Result<
     (
-        BamlRuntime,
+        ResourceArc<runtime::RuntimeResource>,
         BamlMap<String, BamlValue>,
         RuntimeContextManager,
         Option<Vec<Arc<Collector>>>,
         Option<ClientRegistry>,
         Option<TypeBuilder>,
+        Arc<collector::StreamStats>,
     ),
     Error,
 > {
-    let runtime = match BamlRuntime::from_directory(
-        &Path::new(&path),
-        std::env::vars().collect(),
-        internal_baml_core::feature_flags::FeatureFlags::new(),
-    ) {
-        Ok(r) => r,
-        Err(e) => return Err(Error::Term(Box::new(e.to_string()))),
+    let runtime = runtime::resolve(runtime)?;
+    let (params, client_registry) =
+        decode_request(args, client_registry).map_err(errors::invalid_argument)?;
+    let tb = type_builder::from_term(env, tb_elixir, &runtime.inner)?;
+
+    // Collectors are tracked last so that requests with invalid arguments are not recorded
+    let stream_stats = Arc::new(collector::StreamStats::default());
+    let tags = Arc::new(options.tags.clone());
+    let collectors = if collectors.is_empty() {
+        None
+    } else {
+        Some(
+            collectors
+                .iter()
+                .map(|c| c.track(stream_stats.clone(), tags.clone()))
+                .collect(),
+        )
     };
 
+    // Create context
+    let ctx = runtime.inner.create_ctx_manager(
+        BamlValue::String("elixir".to_string()),
+        None, // baml source reader
+    );
+
+    Ok((
+        runtime,
+        params,
+        ctx,
+        collectors,
+        client_registry,
+        tb,
+        stream_stats,
+    ))
+}
+
+fn decode_request<'a>(
+    args: Term<'a>,
+    client_registry: Term<'a>,
+) -> Result<(BamlMap<String, BamlValue>, Option<ClientRegistry>), Error> {
     // Convert args to BamlMap
     let mut params = BamlMap::new();
     if args.is_map() {
@@ -271,18 +451,6 @@ fn prepare_request<'a>(
         return Err(Error::Term(Box::new("Arguments must be a map")));
     }
 
-    // Create context
-    let ctx = runtime.create_ctx_manager(
-        BamlValue::String("elixir".to_string()),
-        None, // baml source reader
-    );
-
-    let collectors = if collectors.is_empty() {
-        None
-    } else {
-        Some(collectors.iter().map(|c| c.inner.clone()).collect())
-    };
-
     let client_registry =
         if client_registry.is_atom() && client_registry.decode::<rustler::Atom>()? == atom::nil() {
             None
@@ -330,44 +498,66 @@ fn prepare_request<'a>(
             )));
         };
 
-    let tb = if tb_elixir.is_list() {
-        let builder = TypeBuilder::new();
+    Ok((params, client_registry))
+}
 
-        // Use the parse_type_builder_spec function from type_builder module
-        if let Err(e) = type_builder::parse_type_builder_spec(env, tb_elixir, &builder) {
-            return Err(e);
-        }
+/// Returned next to the final result as `{:ok, value, meta}` when the caller asks
+/// for it. `trace_id` is the id of the request's function log in collectors.
+struct ResultMeta {
+    trace_id: String,
+}
 
-        Some(builder)
-    } else {
-        None
-    };
+impl ResultMeta {
+    fn new(options: &CallOptions, trace_id: String) -> Option<Self> {
+        options.meta.then_some(ResultMeta { trace_id })
+    }
+}
 
-    Ok((runtime, params, ctx, collectors, client_registry, tb))
+impl Encoder for ResultMeta {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        Term::map_new(env)
+            .map_put(atoms::trace_id(), &self.trace_id)
+            .unwrap()
+    }
 }
 
-fn parse_function_result_call<'a>(env: Env<'a>, result: FunctionResult) -> NifResult<Term<'a>> {
-    let parsed_value = result.parsed();
-    match parsed_value {
-        Some(Ok(response_baml_value)) => {
-            let baml_value = response_baml_value.0.clone().value();
-            let result_term = baml_value_to_term(env, &baml_value)?;
-            Ok((atom::ok(), result_term).encode(env))
+fn with_meta<'a>(
+    env: Env<'a>,
+    tag: rustler::Atom,
+    value: Term<'a>,
+    meta: Option<ResultMeta>,
+) -> Term<'a> {
+    match meta {
+        Some(meta) => (tag, value, meta).encode(env),
+        None => (tag, value).encode(env),
+    }
+}
+
+fn parse_function_result_call<'a>(
+    env: Env<'a>,
+    result: FunctionResult,
+    meta: Option<ResultMeta>,
+) -> Term<'a> {
+    match result.result_with_constraints_content() {
+        Ok(response_baml_value) => {
+            match baml_value_to_term(env, &response_baml_value.0, StateEncoding::Omit) {
+                Ok(result_term) => with_meta(env, atom::ok(), result_term, meta),
+                Err(e) => (atom::error(), encode_error(e)).encode(env),
+            }
         }
-        Some(Err(e)) => Ok((atom::error(), format!("{:?}", e)).encode(env)),
-        None => Ok((atom::error(), "No parsed value available").encode(env)),
+        Err(e) => (atom::error(), BamlError::from_function_result(&result, &e)).encode(env),
     }
 }
 
 fn parse_function_result_stream<'a>(
     env: Env<'a>,
     result: FunctionResult,
+    state: StateEncoding,
 ) -> Result<Term<'a>, String> {
     let parsed_value = result.parsed();
     match parsed_value {
         Some(Ok(response_baml_value)) => {
-            let baml_value = response_baml_value.0.clone().value();
-            let result_term = baml_value_to_term(env, &baml_value)
+            let result_term = baml_value_to_term(env, &response_baml_value.0, state)
                 .map_err(|e| format!("Failed to convert BAML value to term: {:?}", e))?;
             Ok(result_term)
         }
@@ -376,118 +566,474 @@ fn parse_function_result_stream<'a>(
     }
 }
 
+fn parse_function_result_done<'a>(
+    env: Env<'a>,
+    result: FunctionResult,
+    meta: Option<ResultMeta>,
+) -> Term<'a> {
+    match result.result_with_constraints_content() {
+        Ok(result) => match baml_value_to_term(env, &result.0, StateEncoding::Omit) {
+            Ok(result_term) => with_meta(env, atoms::done(), result_term, meta),
+            Err(e) => (atom::error(), encode_error(e)).encode(env),
+        },
+        Err(e) => (atom::error(), BamlError::from_function_result(&result, &e)).encode(env),
+    }
+}
+
+// Results that can't be encoded, e.g. because an atom can't be created, are
+// returned as internal errors like any other failure.
+fn encode_error(err: Error) -> BamlError {
+    BamlError::internal(format!("Failed to convert BAML value to term: {:?}", err))
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
 fn call<'a>(
     env: Env<'a>,
+    pid: LocalPid,
+    reference: Term<'a>,
+    tripwire_resource: Option<ResourceArc<TripWireResource>>,
     function_name: String,
     arguments: Term<'a>,
-    path: String,
+    runtime: Term<'a>,
     collectors: Vec<ResourceArc<collector::CollectorResource>>,
     client_registry: Term<'a>,
     tb: Term<'a>,
+    options: Term<'a>,
 ) -> NifResult<Term<'a>> {
-    let (runtime, params, ctx, collectors, client_registry, tb) =
-        prepare_request(env, arguments, path, collectors, client_registry, tb)?;
+    let options = term_to_call_options(options).map_err(errors::invalid_argument)?;
+    let (runtime, params, ctx, mut collectors, client_registry, tb, stream_stats) =
+        prepare_request(
+            env,
+            arguments,
+            runtime,
+            collectors,
+            client_registry,
+            tb,
+            &options,
+        )?;
+    let tick = with_tick(&options, &mut collectors);
 
-    // Call function synchronously
-    let (result, _trace_id) = runtime.call_function_sync(
-        function_name,
-        &params,
-        &ctx,
-        tb.as_ref(),              // type builder (optional)
-        client_registry.as_ref(), // client registry (optional)
-        collectors,
-        std::env::vars().collect(),
-        None,                // tags
-        TripWire::new(None), // TODO: Add tripwire
-    );
+    let tripwire = tripwire_resource
+        .map(|res| TripWire::new(Some(res.tripwire.0.clone())))
+        .unwrap_or_else(|| TripWire::new(None));
+
+    // Ticks are only emitted while streaming, so calls that want them are streamed
+    // and only the final result is returned.
+    let (result, trace_id) = if tick.is_some() {
+        let stream = match runtime.inner.stream_function(
+            function_name,
+            &params,
+            &ctx,
+            tb.as_ref(),
+            client_registry.as_ref(),
+            collectors,
+            runtime.env_vars(),
+            tripwire,
+            options.tags(),
+        ) {
+            Ok(stream) => stream,
+            Err(e) => return Ok((atom::error(), BamlError::from_anyhow(&e)).encode(env)),
+        };
+        run_stream_sync(
+            env,
+            pid,
+            reference,
+            stream,
+            &ctx,
+            runtime.env_vars(),
+            options.partial_state(),
+            tick,
+            stream_stats,
+            false,
+        )
+    } else {
+        // Call function synchronously
+        let (result, trace_id) = runtime.inner.call_function_sync(
+            function_name,
+            &params,
+            &ctx,
+            tb.as_ref(),              // type builder (optional)
+            client_registry.as_ref(), // client registry (optional)
+            collectors,
+            runtime.env_vars(),
+            options.tags(),
+            tripwire,
+        );
+        (result, trace_id.to_string())
+    };
 
     // Handle result
     match result {
-        Ok(function_result) => parse_function_result_call(env, function_result),
-        Err(e) => Ok((atom::error(), format!("{:?}", e)).encode(env)),
+        Ok(function_result) => Ok(parse_function_result_call(
+            env,
+            function_result,
+            ResultMeta::new(&options, trace_id),
+        )),
+        Err(e) => Ok((atom::error(), BamlError::from_anyhow(&e)).encode(env)),
     }
 }
 
 #[rustler::nif(schedule = "DirtyIo")]
 fn stream<'a>(
     env: Env<'a>,
-    pid: Term<'a>,
+    pid: LocalPid,
     reference: Term<'a>,
     tripwire_resource: Option<ResourceArc<TripWireResource>>,
     function_name: String,
     arguments: Term<'a>,
-    path: String,
+    runtime: Term<'a>,
     collectors: Vec<ResourceArc<collector::CollectorResource>>,
     client_registry: Term<'a>,
     tb: Term<'a>,
+    options: Term<'a>,
 ) -> NifResult<Term<'a>> {
-    let pid = pid.decode::<LocalPid>()?;
-    let (runtime, params, ctx, collectors, client_registry, tb) =
-        prepare_request(env, arguments, path, collectors, client_registry, tb)?;
-
-    let on_event = |r: FunctionResult| {
-        match parse_function_result_stream(env, r) {
-            Ok(result_term) => {
-                let wrapped_result = (reference, (atoms::partial(), result_term)).encode(env);
-                let _ = env.send(&pid, wrapped_result);
-            }
-            Err(_) => {
-                // Do nothing on error because this can happen when
-                // the result cannot be coerced to a BAML value.
-                // This can happen when the result is incomplete.
-                // We'll get the final result and check for a real error then.
-                return;
-            }
-        }
-    };
+    let options = term_to_call_options(options).map_err(errors::invalid_argument)?;
+    let (runtime, params, ctx, mut collectors, client_registry, tb, stream_stats) =
+        prepare_request(
+            env,
+            arguments,
+            runtime,
+            collectors,
+            client_registry,
+            tb,
+            &options,
+        )?;
+    let tick = with_tick(&options, &mut collectors);
 
     let tripwire = tripwire_resource
         .map(|res| TripWire::new(Some(res.tripwire.0.clone())))
         .unwrap_or_else(|| TripWire::new(None));
 
-    let result = runtime.stream_function(
+    let result = runtime.inner.stream_function(
         function_name,
         &params,
         &ctx,
         tb.as_ref(),
         client_registry.as_ref(),
         collectors,
-        std::env::vars().collect(),
+        runtime.env_vars(),
         tripwire,
-        None, // tags
+        options.tags(),
     );
 
     match result {
-        Ok(mut stream) => {
-            let (result, _trace_id) = stream.run_sync(
-                None::<fn()>,
-                Some(on_event),
+        Ok(stream) => {
+            let (result, trace_id) = run_stream_sync(
+                env,
+                pid,
+                reference,
+                stream,
                 &ctx,
-                None,
-                None,
-                std::env::vars().collect(),
+                runtime.env_vars(),
+                options.partial_state(),
+                tick,
+                stream_stats,
+                true,
             );
             match result {
-                Ok(r) => match r.parsed() {
-                    Some(Ok(result)) => {
-                        let baml_value = result.0.clone().value();
-                        let result_term = baml_value_to_term(env, &baml_value)?;
-                        Ok((atoms::done(), result_term).encode(env))
-                    }
-                    Some(Err(e)) => Ok((atom::error(), format!("{:?}", e)).encode(env)),
-                    None => Ok((atom::error(), "No parsed value available").encode(env)),
-                },
-                Err(e) => Ok((atom::error(), format!("{:?}", e)).encode(env)),
+                Ok(r) => Ok(parse_function_result_done(
+                    env,
+                    r,
+                    ResultMeta::new(&options, trace_id),
+                )),
+                Err(e) => Ok((atom::error(), BamlError::from_anyhow(&e)).encode(env)),
             }
         }
-        Err(e) => Ok((atom::error(), format!("{:?}", e)).encode(env)),
+        Err(e) => Ok((atom::error(), BamlError::from_anyhow(&e)).encode(env)),
     }
 }
 
+/// Like `call`, but returns as soon as the request is prepared. The LLM call runs
+/// on the shared tokio runtime and its result is sent to `pid` as `{reference, result}`.
+/// Preparing the request may read local media files, so it runs on a dirty IO scheduler.
+#[rustler::nif(schedule = "DirtyIo")]
+fn call_async<'a>(
+    env: Env<'a>,
+    pid: LocalPid,
+    reference: Term<'a>,
+    tripwire_resource: Option<ResourceArc<TripWireResource>>,
+    function_name: String,
+    arguments: Term<'a>,
+    runtime: Term<'a>,
+    collectors: Vec<ResourceArc<collector::CollectorResource>>,
+    client_registry: Term<'a>,
+    tb: Term<'a>,
+    options: Term<'a>,
+) -> NifResult<rustler::Atom> {
+    let options = term_to_call_options(options).map_err(errors::invalid_argument)?;
+    let (runtime, params, ctx, mut collectors, client_registry, tb, stream_stats) =
+        prepare_request(
+            env,
+            arguments,
+            runtime,
+            collectors,
+            client_registry,
+            tb,
+            &options,
+        )?;
+    let tick = with_tick(&options, &mut collectors);
+    let reply = Arc::new(async_runtime::Reply::new(pid, reference));
+
+    let tripwire = tripwire_resource
+        .map(|res| TripWire::new(Some(res.tripwire.0.clone())))
+        .unwrap_or_else(|| TripWire::new(None));
+
+    async_runtime::spawn(reply.clone(), async move {
+        // Same as `call`: calls that want ticks are streamed.
+        let (result, trace_id) = if tick.is_some() {
+            let stream = match runtime.inner.stream_function(
+                function_name,
+                &params,
+                &ctx,
+                tb.as_ref(),
+                client_registry.as_ref(),
+                collectors,
+                runtime.env_vars(),
+                tripwire,
+                options.tags(),
+            ) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    reply.send(|env| (atom::error(), BamlError::from_anyhow(&e)).encode(env));
+                    return;
+                }
+            };
+            run_stream(
+                reply.clone(),
+                stream,
+                &ctx,
+                runtime.env_vars(),
+                options.partial_state(),
+                tick,
+                stream_stats,
+                false,
+            )
+            .await
+        } else {
+            let (result, trace_id) = runtime
+                .inner
+                .call_function(
+                    function_name,
+                    &params,
+                    &ctx,
+                    tb.as_ref(),
+                    client_registry.as_ref(),
+                    collectors,
+                    runtime.env_vars(),
+                    options.tags(),
+                    tripwire,
+                )
+                .await;
+            (result, trace_id.to_string())
+        };
+
+        let meta = ResultMeta::new(&options, trace_id);
+        reply.send(|env| match result {
+            Ok(function_result) => parse_function_result_call(env, function_result, meta),
+            Err(e) => (atom::error(), BamlError::from_anyhow(&e)).encode(env),
+        });
+    });
+
+    Ok(atoms::ok())
+}
+
+/// Like `stream`, but returns as soon as the request is prepared. Partial results
+/// and the final result are sent to `pid` as `{reference, message}`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn stream_async<'a>(
+    env: Env<'a>,
+    pid: LocalPid,
+    reference: Term<'a>,
+    tripwire_resource: Option<ResourceArc<TripWireResource>>,
+    function_name: String,
+    arguments: Term<'a>,
+    runtime: Term<'a>,
+    collectors: Vec<ResourceArc<collector::CollectorResource>>,
+    client_registry: Term<'a>,
+    tb: Term<'a>,
+    options: Term<'a>,
+) -> NifResult<rustler::Atom> {
+    let options = term_to_call_options(options).map_err(errors::invalid_argument)?;
+    let (runtime, params, ctx, mut collectors, client_registry, tb, stream_stats) =
+        prepare_request(
+            env,
+            arguments,
+            runtime,
+            collectors,
+            client_registry,
+            tb,
+            &options,
+        )?;
+    let tick = with_tick(&options, &mut collectors);
+    let reply = Arc::new(async_runtime::Reply::new(pid, reference));
+
+    let tripwire = tripwire_resource
+        .map(|res| TripWire::new(Some(res.tripwire.0.clone())))
+        .unwrap_or_else(|| TripWire::new(None));
+
+    async_runtime::spawn(reply.clone(), async move {
+        let result = runtime.inner.stream_function(
+            function_name,
+            &params,
+            &ctx,
+            tb.as_ref(),
+            client_registry.as_ref(),
+            collectors,
+            runtime.env_vars(),
+            tripwire,
+            options.tags(),
+        );
+
+        let stream = match result {
+            Ok(stream) => stream,
+            Err(e) => {
+                reply.send(|env| (atom::error(), BamlError::from_anyhow(&e)).encode(env));
+                return;
+            }
+        };
+
+        let (result, trace_id) = run_stream(
+            reply.clone(),
+            stream,
+            &ctx,
+            runtime.env_vars(),
+            options.partial_state(),
+            tick,
+            stream_stats,
+            true,
+        )
+        .await;
+
+        let meta = ResultMeta::new(&options, trace_id);
+        reply.send(|env| match result {
+            Ok(r) => parse_function_result_done(env, r, meta),
+            Err(e) => (atom::error(), BamlError::from_anyhow(&e)).encode(env),
+        });
+    });
+
+    Ok(atoms::ok())
+}
+
+// Attaches the collector that tick messages snapshot the function log from.
+fn with_tick(
+    options: &CallOptions,
+    collectors: &mut Option<Vec<Arc<Collector>>>,
+) -> Option<Arc<tick::TickState>> {
+    if !options.tick {
+        return None;
+    }
+    let tick = tick::TickState::new();
+    collectors
+        .get_or_insert_with(Vec::new)
+        .push(tick.collector());
+    Some(tick)
+}
+
+/// Runs `stream` on the current thread. Partial results are sent to `pid` as
+/// `{reference, {:partial, value}}` when `send_partials` is set, and ticks as
+/// `{reference, {:tick, info}}` when `tick` is given. Returns the result with its trace id.
+#[allow(clippy::too_many_arguments)]
+fn run_stream_sync<'a>(
+    env: Env<'a>,
+    pid: LocalPid,
+    reference: Term<'a>,
+    mut stream: FunctionResultStream,
+    ctx: &RuntimeContextManager,
+    env_vars: HashMap<String, String>,
+    state: StateEncoding,
+    tick: Option<Arc<tick::TickState>>,
+    stream_stats: Arc<collector::StreamStats>,
+    send_partials: bool,
+) -> (anyhow::Result<FunctionResult>, String) {
+    let on_tick = tick.clone().map(|tick| {
+        move || {
+            let _ = env.send(&pid, (reference, tick.to_term(env)).encode(env));
+        }
+    });
+
+    let on_event = |r: FunctionResult| {
+        stream_stats.record(&r);
+        if let Some(tick) = &tick {
+            tick.record(&r);
+        }
+        if !send_partials {
+            return;
+        }
+        match parse_function_result_stream(env, r, state) {
+            Ok(result_term) => {
+                let wrapped_result = (reference, (atoms::partial(), result_term)).encode(env);
+                let _ = env.send(&pid, wrapped_result);
+            }
+            Err(_) => {
+                // Do nothing on error because this can happen when
+                // the result cannot be coerced to a BAML value.
+                // This can happen when the result is incomplete.
+                // We'll get the final result and check for a real error then.
+            }
+        }
+    };
+
+    let (result, trace_id) = stream.run_sync(on_tick, Some(on_event), ctx, None, None, env_vars);
+    (result, trace_id.to_string())
+}
+
+/// Async counterpart of `run_stream_sync`, replying through `reply`.
+#[allow(clippy::too_many_arguments)]
+async fn run_stream(
+    reply: Arc<async_runtime::Reply>,
+    mut stream: FunctionResultStream,
+    ctx: &RuntimeContextManager,
+    env_vars: HashMap<String, String>,
+    state: StateEncoding,
+    tick: Option<Arc<tick::TickState>>,
+    stream_stats: Arc<collector::StreamStats>,
+    send_partials: bool,
+) -> (anyhow::Result<FunctionResult>, String) {
+    let tick_reply = reply.clone();
+    let on_tick = tick
+        .clone()
+        .map(move |tick| move || tick_reply.send(|env| tick.to_term(env)));
+
+    let on_event = move |r: FunctionResult| {
+        stream_stats.record(&r);
+        if let Some(tick) = &tick {
+            tick.record(&r);
+        }
+        // Same as `stream`: incomplete results that cannot be coerced yet are
+        // skipped, the final result reports real errors.
+        if !send_partials || !matches!(r.parsed(), Some(Ok(_))) {
+            return;
+        }
+        reply.try_send(|env| {
+            parse_function_result_stream(env, r, state)
+                .ok()
+                .map(|result_term| (atoms::partial(), result_term).encode(env))
+        });
+    };
+
+    let (result, trace_id) = stream
+        .run(on_tick, Some(on_event), ctx, None, None, env_vars)
+        .await;
+    (result, trace_id.to_string())
+}
+
 #[rustler::nif]
-fn collector_new(name: Option<String>) -> ResourceArc<collector::CollectorResource> {
-    collector::CollectorResource::new(name)
+fn collector_new(
+    name: Option<String>,
+    options: Term,
+) -> NifResult<ResourceArc<collector::CollectorResource>> {
+    let options = collector::CollectorOptions::from_term(options)?;
+    Ok(collector::CollectorResource::new(name, options))
+}
+
+#[rustler::nif]
+fn collector_clear(collector: ResourceArc<collector::CollectorResource>) -> usize {
+    collector.clear()
+}
+
+#[rustler::nif]
+fn collector_drain(collector: ResourceArc<collector::CollectorResource>) -> Vec<FunctionLog> {
+    collector.drain()
 }
 
 #[rustler::nif]
@@ -503,20 +1049,47 @@ fn collector_last_function_log(
 }
 
 #[rustler::nif]
-fn parse_baml(env: Env, path: Option<String>) -> NifResult<Term> {
-    let path = path.unwrap_or_else(|| "baml_src".to_string());
-
-    // Create runtime
-    let runtime = match BamlRuntime::from_directory(
-        &Path::new(&path),
-        std::env::vars().collect(),
-        internal_baml_core::feature_flags::FeatureFlags::new(),
-    ) {
-        Ok(r) => r,
-        Err(e) => return Err(Error::Term(Box::new(e.to_string()))),
-    };
+fn collector_logs(
+    collector: ResourceArc<collector::CollectorResource>,
+    tags: Term,
+) -> NifResult<Vec<FunctionLog>> {
+    let tags = term_to_tags(tags)?;
+    Ok(collector.function_logs(&tags))
+}
 
-    let ir = runtime.ir.clone();
+#[rustler::nif]
+fn collector_log_by_id(
+    collector: ResourceArc<collector::CollectorResource>,
+    id: String,
+) -> Option<FunctionLog> {
+    collector.function_log_by_id(&id)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn runtime_new(
+    path: String,
+    env_vars: Option<HashMap<String, String>>,
+    feature_flags: Vec<String>,
+) -> Result<ResourceArc<runtime::RuntimeResource>, BamlError> {
+    let feature_flags = runtime::feature_flags_from_list(feature_flags)?;
+    runtime::RuntimeResource::from_directory(&path, env_vars, feature_flags)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn runtime_from_files(
+    files: HashMap<String, String>,
+    env_vars: Option<HashMap<String, String>>,
+    feature_flags: Vec<String>,
+) -> Result<ResourceArc<runtime::RuntimeResource>, BamlError> {
+    let feature_flags = runtime::feature_flags_from_list(feature_flags)?;
+    runtime::RuntimeResource::from_files(files, env_vars, feature_flags)
+}
+
+#[rustler::nif]
+fn parse_baml<'a>(env: Env<'a>, runtime: Term<'a>) -> NifResult<Term<'a>> {
+    let runtime = runtime::resolve(runtime)?;
+
+    let ir = runtime.inner.ir.clone();
 
     // Create a map of the classes and their fields along with their types
     let mut class_fields = HashMap::new();
@@ -735,6 +1308,15 @@ fn create_tripwire() -> ResourceArc<TripWireResource> {
     })
 }
 
+/// Panics on the shared tokio runtime, so tests can check that callers still get
+/// `{reference, {:error, %{type: :internal_error}}}`.
+#[rustler::nif]
+fn spawn_panicking_task(pid: LocalPid, reference: Term) -> rustler::Atom {
+    let reply = Arc::new(async_runtime::Reply::new(pid, reference));
+    async_runtime::spawn(reply, async { panic!("spawn_panicking_task") });
+    atoms::ok()
+}
+
 #[rustler::nif]
 fn abort_tripwire(tripwire_res: ResourceArc<TripWireResource>) -> rustler::Atom {
     if let Ok(mut guard) = tripwire_res.trigger.lock() {
@@ -0,0 +1,215 @@
+use baml_types::{BamlMedia, BamlMediaContent, BamlMediaType};
+use base64::Engine;
+use rustler::{types::atom, Atom, Encoder, Env, Error, MapIterator, NifResult, Term};
+use std::path::Path;
+
+mod atoms {
+    rustler::atoms! {
+        baml_media = "__baml_media__",
+        image,
+        audio,
+        pdf,
+        video,
+        url,
+        base64,
+        file,
+        media_type,
+    }
+}
+
+/// Media values are exchanged with Elixir as tagged maps:
+/// `%{__baml_media__: :image, url: ..., base64: ..., file: ..., media_type: ...}`,
+/// with the unused content keys set to nil.
+pub fn is_media_term(term: Term) -> bool {
+    term.is_map() && term.map_get(atoms::baml_media()).is_ok()
+}
+
+pub fn media_to_term<'a>(env: Env<'a>, media: &BamlMedia) -> NifResult<Term<'a>> {
+    let map = Term::map_new(env)
+        .map_put(atoms::baml_media(), media_type_to_atom(&media.media_type))?
+        .map_put(atoms::media_type(), media.mime_type.clone())?;
+
+    let (url, base64, file) = match &media.content {
+        BamlMediaContent::Url(u) => (Some(u.url.clone()), None, None),
+        BamlMediaContent::Base64(b) => (None, Some(b.base64.clone()), None),
+        // `relpath` is relative to the BAML file the media was declared in, while files
+        // passed back in as arguments are read relative to the current directory
+        BamlMediaContent::File(f) => {
+            let dir = f.span_path.parent().unwrap_or_else(|| Path::new(""));
+            let path = dir.join(&f.relpath);
+            (None, None, Some(path.to_string_lossy().to_string()))
+        }
+    };
+
+    map.map_put(atoms::url(), url)?
+        .map_put(atoms::base64(), base64)?
+        .map_put(atoms::file(), file)
+}
+
+/// Decodes a tagged media map (as built by `BamlElixir.Media`) and validates it
+/// before any request is sent. Local files are read and sent as base64.
+pub fn term_to_media(term: Term) -> Result<BamlMedia, Error> {
+    let mut media_type = None;
+    let mut url = None;
+    let mut base64 = None;
+    let mut file = None;
+    let mut mime_type = None;
+
+    let iter = MapIterator::new(term).ok_or(Error::Term(Box::new("Invalid media map")))?;
+    for (key_term, value_term) in iter {
+        if !key_term.is_atom() {
+            continue;
+        }
+        let key = key_term.atom_to_string()?;
+        match key.as_str() {
+            "__baml_media__" => media_type = Some(atom_to_media_type(value_term)?),
+            "url" => url = optional_string(value_term)?,
+            "base64" => base64 = optional_string(value_term)?,
+            "file" => file = optional_string(value_term)?,
+            "media_type" => mime_type = optional_string(value_term)?,
+            _ => {}
+        }
+    }
+
+    let media_type = media_type.ok_or(Error::Term(Box::new("Media missing __baml_media__")))?;
+    match (url, base64, file) {
+        (Some(url), None, None) => {
+            if url.is_empty() {
+                return Err(Error::Term(Box::new("Media url must not be empty")));
+            }
+            let mime_type = mime_type.or_else(|| mime_type_from_path(&url));
+            validate_mime_type(&media_type, mime_type.as_deref())?;
+            Ok(BamlMedia::url(media_type, url, mime_type))
+        }
+        (None, Some(base64), None) => {
+            // Accept data URLs such as "data:image/png;base64,..." as well as bare base64
+            let (data_url_mime_type, data) = split_data_url(&base64);
+            validate_base64(data)?;
+            let mime_type = mime_type.or(data_url_mime_type);
+            validate_mime_type(&media_type, mime_type.as_deref())?;
+            Ok(BamlMedia::base64(media_type, data.to_string(), mime_type))
+        }
+        (None, None, Some(path)) => {
+            let bytes = std::fs::read(&path).map_err(|e| {
+                Error::Term(Box::new(format!(
+                    "Failed to read media file {}: {}",
+                    path, e
+                )))
+            })?;
+            let mime_type = mime_type.or_else(|| mime_type_from_path(&path));
+            validate_mime_type(&media_type, mime_type.as_deref())?;
+            Ok(BamlMedia::base64(
+                media_type,
+                base64::engine::general_purpose::STANDARD.encode(bytes),
+                mime_type,
+            ))
+        }
+        _ => Err(Error::Term(Box::new(
+            "Media must have exactly one of url, base64 or file",
+        ))),
+    }
+}
+
+// Decodes into a discarded sink, so large media is validated without a copy.
+fn validate_base64(data: &str) -> Result<(), Error> {
+    let mut decoder = base64::read::DecoderReader::new(
+        data.as_bytes(),
+        &base64::engine::general_purpose::STANDARD,
+    );
+    std::io::copy(&mut decoder, &mut std::io::sink())
+        .map(|_| ())
+        .map_err(|e| Error::Term(Box::new(format!("Invalid base64 media: {}", e))))
+}
+
+fn split_data_url(value: &str) -> (Option<String>, &str) {
+    if let Some(rest) = value.strip_prefix("data:") {
+        if let Some((header, data)) = rest.split_once(',') {
+            let mime_type = header.trim_end_matches(";base64");
+            if !mime_type.is_empty() {
+                return (Some(mime_type.to_string()), data);
+            }
+            return (None, data);
+        }
+    }
+    (None, value)
+}
+
+fn mime_type_from_path(path: &str) -> Option<String> {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    let mime_type = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "pdf" => "application/pdf",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        "mpeg" => "video/mpeg",
+        _ => return None,
+    };
+    Some(mime_type.to_string())
+}
+
+fn validate_mime_type(media_type: &BamlMediaType, mime_type: Option<&str>) -> Result<(), Error> {
+    let Some(mime_type) = mime_type else {
+        return Ok(());
+    };
+    let valid = match media_type {
+        BamlMediaType::Image => mime_type.starts_with("image/"),
+        BamlMediaType::Audio => mime_type.starts_with("audio/"),
+        BamlMediaType::Pdf => mime_type == "application/pdf",
+        BamlMediaType::Video => mime_type.starts_with("video/"),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Term(Box::new(format!(
+            "Media type {} does not match {:?} media",
+            mime_type, media_type
+        ))))
+    }
+}
+
+fn media_type_to_atom(media_type: &BamlMediaType) -> Atom {
+    match media_type {
+        BamlMediaType::Image => atoms::image(),
+        BamlMediaType::Audio => atoms::audio(),
+        BamlMediaType::Pdf => atoms::pdf(),
+        BamlMediaType::Video => atoms::video(),
+    }
+}
+
+fn atom_to_media_type(term: Term) -> Result<BamlMediaType, Error> {
+    let name = if term.is_atom() {
+        term.atom_to_string()?
+    } else {
+        term.decode::<String>()?
+    };
+    match name.as_str() {
+        "image" => Ok(BamlMediaType::Image),
+        "audio" => Ok(BamlMediaType::Audio),
+        "pdf" => Ok(BamlMediaType::Pdf),
+        "video" => Ok(BamlMediaType::Video),
+        other => Err(Error::Term(Box::new(format!(
+            "Unsupported media type: {}",
+            other
+        )))),
+    }
+}
+
+fn optional_string(term: Term) -> Result<Option<String>, Error> {
+    if term.is_atom() && term.decode::<Atom>()? == atom::nil() {
+        Ok(None)
+    } else {
+        Ok(Some(term.decode()?))
+    }
+}
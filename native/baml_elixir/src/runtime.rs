@@ -0,0 +1,98 @@
+use crate::errors::{invalid_argument, BamlError};
+use baml_runtime::BamlRuntime;
+use internal_baml_core::feature_flags::FeatureFlags;
+use rustler::{types::atom, Atom, Error, Resource, ResourceArc, Term};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[rustler::resource_impl()]
+impl Resource for RuntimeResource {}
+
+pub struct RuntimeResource {
+    pub inner: BamlRuntime,
+    /// The `env` given when the runtime was created. Without it, every call reads the
+    /// environment of the process, so that rotated API keys are picked up.
+    env_vars: Option<HashMap<String, String>>,
+}
+
+impl RuntimeResource {
+    pub fn from_directory(
+        path: &str,
+        env_vars: Option<HashMap<String, String>>,
+        feature_flags: FeatureFlags,
+    ) -> Result<ResourceArc<RuntimeResource>, BamlError> {
+        let runtime = BamlRuntime::from_directory(
+            Path::new(path),
+            env_vars_or_default(env_vars.clone()),
+            feature_flags,
+        )
+        .map_err(|e| BamlError::configuration(e.to_string()))?;
+        Ok(ResourceArc::new(RuntimeResource {
+            inner: runtime,
+            env_vars,
+        }))
+    }
+
+    pub fn from_files(
+        files: HashMap<String, String>,
+        env_vars: Option<HashMap<String, String>>,
+        feature_flags: FeatureFlags,
+    ) -> Result<ResourceArc<RuntimeResource>, BamlError> {
+        let runtime = BamlRuntime::from_file_content(
+            "baml_src",
+            &files,
+            env_vars_or_default(env_vars.clone()),
+            feature_flags,
+        )
+        .map_err(|e| BamlError::configuration(e.to_string()))?;
+        Ok(ResourceArc::new(RuntimeResource {
+            inner: runtime,
+            env_vars,
+        }))
+    }
+
+    /// The environment variables to run a call with.
+    pub fn env_vars(&self) -> HashMap<String, String> {
+        env_vars_or_default(self.env_vars.clone())
+    }
+}
+
+pub fn feature_flags_from_list(flags: Vec<String>) -> Result<FeatureFlags, BamlError> {
+    FeatureFlags::from_vec(flags).map_err(|errors| BamlError::configuration(errors.join(", ")))
+}
+
+fn env_vars_or_default(env_vars: Option<HashMap<String, String>>) -> HashMap<String, String> {
+    env_vars.unwrap_or_else(|| std::env::vars().collect())
+}
+
+/// Accepts a runtime created with `runtime_new`/`runtime_from_files`, a path to a
+/// baml_src directory, or a map of file names to BAML source. Paths and file maps
+/// are compiled on every call, so long-lived callers should prefer passing a runtime.
+pub fn resolve(term: Term) -> Result<ResourceArc<RuntimeResource>, Error> {
+    if let Ok(runtime) = term.decode::<ResourceArc<RuntimeResource>>() {
+        return Ok(runtime);
+    }
+
+    if term.is_map() {
+        let files = term.decode::<HashMap<String, String>>().map_err(|_| {
+            invalid_argument(Error::Term(Box::new(
+                "BAML files must be a map of file name to source",
+            )))
+        })?;
+        return RuntimeResource::from_files(files, None, FeatureFlags::new())
+            .map_err(|e| Error::Term(Box::new(e)));
+    }
+
+    let path = if term.is_atom() && term.decode::<Atom>()? == atom::nil() {
+        "baml_src".to_string()
+    } else {
+        term.decode::<String>().map_err(|_| {
+            invalid_argument(Error::Term(Box::new(
+                "Expected a runtime or a path to a baml_src directory",
+            )))
+        })?
+    };
+
+    RuntimeResource::from_directory(&path, None, FeatureFlags::new())
+        .map_err(|e| Error::Term(Box::new(e)))
+}
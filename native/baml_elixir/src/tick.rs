@@ -0,0 +1,64 @@
+use crate::collector::FunctionLog;
+use baml_runtime::internal::llm_client::LLMResponse;
+use baml_runtime::tracingv2::storage::storage::Collector;
+use baml_runtime::FunctionResult;
+use rustler::{Encoder, Env, Term};
+use std::sync::{Arc, Mutex};
+
+mod atoms {
+    rustler::atoms! {
+        tick,
+        raw_text,
+        function_log,
+        thinking,
+    }
+}
+
+/// Tracks an in-flight request so that `{:tick, info}` messages can report the raw
+/// text received so far and a snapshot of the function log.
+pub struct TickState {
+    collector: Arc<Collector>,
+    raw_text: Mutex<String>,
+}
+
+impl TickState {
+    pub fn new() -> Arc<TickState> {
+        Arc::new(TickState {
+            collector: Arc::new(Collector::new(Some("on_tick".to_string()))),
+            raw_text: Mutex::new(String::new()),
+        })
+    }
+
+    /// The collector that has to be attached to the request to snapshot its function log.
+    pub fn collector(&self) -> Arc<Collector> {
+        self.collector.clone()
+    }
+
+    /// Remembers the raw text accumulated by a streamed event.
+    pub fn record(&self, result: &FunctionResult) {
+        if let LLMResponse::Success(response) = result.llm_response() {
+            *self.raw_text.lock().unwrap() = response.content.clone();
+        }
+    }
+
+    pub fn to_term<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let function_log = self
+            .collector
+            .last_function_log()
+            .map(|log| FunctionLog::new(log, None, Default::default(), Default::default()));
+        let thinking = function_log
+            .as_ref()
+            .map(|log| log.thinking_chunks())
+            .unwrap_or_default();
+
+        let info = Term::map_new(env)
+            .map_put(atoms::raw_text(), self.raw_text.lock().unwrap().clone())
+            .unwrap()
+            .map_put(atoms::function_log(), function_log)
+            .unwrap()
+            .map_put(atoms::thinking(), thinking)
+            .unwrap();
+
+        (atoms::tick(), info).encode(env)
+    }
+}
@@ -1,12 +1,72 @@
+use crate::errors::{invalid_argument, BamlError, Diagnostic};
 use crate::Error;
 use baml_runtime::type_builder::{TypeBuilder, WithMeta};
-use baml_types::{ir_type::UnionConstructor, LiteralValue, TypeIR};
+use baml_runtime::BamlRuntime;
+use baml_types::{
+    ir_type::UnionConstructor, Constraint, ConstraintLevel, JinjaExpression, LiteralValue, TypeIR,
+};
+use internal_baml_core::ast::parse_type_builder_contents_from_str;
+use internal_baml_core::internal_baml_diagnostics::{Diagnostics, SourceFile, Span};
 use rustler::{types::atom, Atom, Env, MapIterator, Term};
+use std::path::PathBuf;
 
-pub fn parse_type_builder_spec<'a>(
+/// Builds the type builder of a call, or `None` if no type builder was given. BAML
+/// sources are added once the other items are in place, so they can refer to them.
+pub fn from_term<'a>(
+    env: Env<'a>,
+    term: Term<'a>,
+    runtime: &BamlRuntime,
+) -> Result<Option<TypeBuilder>, Error> {
+    if !term.is_list() {
+        return Ok(None);
+    }
+
+    let builder = TypeBuilder::new();
+    let mut sources = Vec::new();
+    parse_type_builder_spec(env, term, &builder, &mut sources).map_err(invalid_argument)?;
+    for source in sources {
+        builder.add_baml(&source, runtime).map_err(|e| {
+            Error::Term(Box::new(BamlError::type_builder(
+                &e,
+                source_diagnostics(&source),
+            )))
+        })?;
+    }
+    Ok(Some(builder))
+}
+
+// `add_baml` only reports diagnostics rendered into its error message, so the source is
+// parsed again to get them as BAML reports them. Sources that parse but fail to validate
+// against the runtime's types have no parser diagnostics.
+fn source_diagnostics(source: &str) -> Vec<Diagnostic> {
+    let path = PathBuf::from("TypeBuilder::add_baml");
+    let mut diagnostics = Diagnostics::new(path.clone());
+    diagnostics.set_source(&SourceFile::from((path, source.to_string())));
+    let _ = parse_type_builder_contents_from_str(source, &mut diagnostics);
+
+    // Lines are numbered from 1, like in the rendered diagnostics. Spans outside of the
+    // source or within a character have no line.
+    let line = |span: &Span| {
+        source
+            .get(..span.start)
+            .map(|before| before.matches('\n').count() + 1)
+    };
+    let errors = diagnostics
+        .errors()
+        .iter()
+        .map(|error| Diagnostic::new(true, error.message(), line(error.span())));
+    let warnings = diagnostics
+        .warnings()
+        .iter()
+        .map(|warning| Diagnostic::new(false, warning.message(), line(warning.span())));
+    errors.chain(warnings).collect()
+}
+
+fn parse_type_builder_spec<'a>(
     env: Env<'a>,
     term: Term<'a>,
     builder: &TypeBuilder,
+    sources: &mut Vec<String>,
 ) -> Result<(), Error> {
     if !term.is_list() {
         return Err(Error::Term(Box::new(
@@ -17,7 +77,7 @@ pub fn parse_type_builder_spec<'a>(
     // New format: list of TypeBuilder structs
     let list: Vec<Term> = term.decode()?;
     for item in list {
-        parse_type_builder_item(env, item, builder)?;
+        parse_type_builder_item(env, item, builder, sources)?;
     }
     Ok(())
 }
@@ -26,6 +86,7 @@ fn parse_type_builder_item<'a>(
     env: Env<'a>,
     term: Term<'a>,
     builder: &TypeBuilder,
+    sources: &mut Vec<String>,
 ) -> Result<(), Error> {
     if !term.is_map() {
         return Err(Error::Term(Box::new("TypeBuilder item must be a map")));
@@ -58,6 +119,9 @@ fn parse_type_builder_item<'a>(
         Some("Elixir.BamlElixir.TypeBuilder.Enum") => {
             parse_enum_item(term, builder)?;
         }
+        Some("Elixir.BamlElixir.TypeBuilder.Baml") => {
+            sources.push(parse_baml_item(term)?);
+        }
         Some(other) => {
             return Err(Error::Term(Box::new(format!(
                 "Unsupported TypeBuilder struct: {}",
@@ -72,6 +136,18 @@ fn parse_type_builder_item<'a>(
     Ok(())
 }
 
+fn parse_baml_item(baml_term: Term) -> Result<String, Error> {
+    let iter = MapIterator::new(baml_term).ok_or(Error::Term(Box::new("Invalid BAML map")))?;
+    for (key_term, value_term) in iter {
+        if term_to_string(key_term)? == "source" {
+            return value_term
+                .decode::<String>()
+                .map_err(|_| Error::Term(Box::new("BAML source must be a string")));
+        }
+    }
+    Err(Error::Term(Box::new("BAML missing source field")))
+}
+
 fn parse_class_item<'a>(
     env: Env<'a>,
     class_term: Term<'a>,
@@ -84,6 +160,8 @@ fn parse_class_item<'a>(
     let iter = MapIterator::new(class_term).ok_or(Error::Term(Box::new("Invalid class map")))?;
     let mut class_name = None;
     let mut fields = None;
+    let mut description = None;
+    let mut alias = None;
 
     for (key_term, value_term) in iter {
         let key = term_to_string(key_term)?;
@@ -94,6 +172,12 @@ fn parse_class_item<'a>(
             "fields" => {
                 fields = Some(value_term);
             }
+            "description" => {
+                description = optional_string(value_term)?;
+            }
+            "alias" => {
+                alias = optional_string(value_term)?;
+            }
             _ => {}
         }
     }
@@ -105,6 +189,14 @@ fn parse_class_item<'a>(
     let cls = builder.upsert_class(&class_name);
     let cls = cls.lock().unwrap();
 
+    // Same as `@@description` and `@@alias`
+    if let Some(desc) = description {
+        cls.with_meta("description", baml_types::BamlValue::String(desc));
+    }
+    if let Some(alias) = alias {
+        cls.with_meta("alias", baml_types::BamlValue::String(alias));
+    }
+
     if fields.is_list() {
         let field_list: Vec<Term> = fields.decode()?;
         for field_term in field_list {
@@ -125,6 +217,8 @@ fn parse_enum_item<'a>(enum_term: Term<'a>, builder: &TypeBuilder) -> Result<(),
     let iter = MapIterator::new(enum_term).ok_or(Error::Term(Box::new("Invalid enum map")))?;
     let mut enum_name = None;
     let mut values = None;
+    let mut description = None;
+    let mut alias = None;
 
     for (key_term, value_term) in iter {
         let key = term_to_string(key_term)?;
@@ -135,6 +229,12 @@ fn parse_enum_item<'a>(enum_term: Term<'a>, builder: &TypeBuilder) -> Result<(),
             "values" => {
                 values = Some(value_term);
             }
+            "description" => {
+                description = optional_string(value_term)?;
+            }
+            "alias" => {
+                alias = optional_string(value_term)?;
+            }
             _ => {}
         }
     }
@@ -146,6 +246,13 @@ fn parse_enum_item<'a>(enum_term: Term<'a>, builder: &TypeBuilder) -> Result<(),
     let enum_builder = builder.upsert_enum(&enum_name);
     let enum_builder = enum_builder.lock().unwrap();
 
+    if let Some(desc) = description {
+        enum_builder.with_meta("description", baml_types::BamlValue::String(desc));
+    }
+    if let Some(alias) = alias {
+        enum_builder.with_meta("alias", baml_types::BamlValue::String(alias));
+    }
+
     if values.is_list() {
         let value_list: Vec<Term> = values.decode()?;
         for value_term in value_list {
@@ -166,6 +273,8 @@ fn parse_enum_value_item<'a>(
         MapIterator::new(value_term).ok_or(Error::Term(Box::new("Invalid enum value map")))?;
     let mut value_name = None;
     let mut description = None;
+    let mut alias = None;
+    let mut skip = None;
 
     for (key_term, value_term) in iter {
         let key = term_to_string(key_term)?;
@@ -190,6 +299,12 @@ fn parse_enum_value_item<'a>(
                     description = Some(term_to_string(value_term)?);
                 }
             }
+            "alias" => {
+                alias = optional_string(value_term)?;
+            }
+            "skip" => {
+                skip = optional_bool(value_term)?;
+            }
             _ => {}
         }
     }
@@ -204,6 +319,12 @@ fn parse_enum_value_item<'a>(
     if let Some(desc) = description {
         value_builder.with_meta("description", baml_types::BamlValue::String(desc));
     }
+    if let Some(alias) = alias {
+        value_builder.with_meta("alias", baml_types::BamlValue::String(alias));
+    }
+    if let Some(skip) = skip {
+        value_builder.with_meta("skip", baml_types::BamlValue::Bool(skip));
+    }
 
     Ok(())
 }
@@ -223,6 +344,9 @@ fn parse_field_item<'a>(
     let mut field_name = None;
     let mut field_type = None;
     let mut description = None;
+    let mut alias = None;
+    let mut skip = None;
+    let mut constraints = Vec::new();
 
     for (key_term, value_term) in iter {
         let key = term_to_string(key_term)?;
@@ -240,6 +364,15 @@ fn parse_field_item<'a>(
                     description = Some(term_to_string(value_term)?);
                 }
             }
+            "alias" => {
+                alias = optional_string(value_term)?;
+            }
+            "skip" => {
+                skip = optional_bool(value_term)?;
+            }
+            "constraints" => {
+                constraints = parse_constraints(value_term)?;
+            }
             _ => {}
         }
     }
@@ -247,13 +380,15 @@ fn parse_field_item<'a>(
     let field_name = field_name.ok_or(Error::Term(Box::new("Missing field name")))?;
     let field_type_term = field_type.ok_or(Error::Term(Box::new("Missing field type")))?;
 
-    let type_ir = parse_field_type(
+    let mut type_ir = parse_field_type(
         env,
         field_type_term,
         builder,
         Some(parent_class),
         Some(&field_name),
     )?;
+    // Same as `@check` and `@assert` on a field: they constrain the field's type
+    type_ir.meta_mut().constraints.extend(constraints);
 
     // Add the field to the class
     let property = cls.upsert_property(&field_name);
@@ -264,6 +399,13 @@ fn parse_field_item<'a>(
     if let Some(desc) = description {
         property.with_meta("description", baml_types::BamlValue::String(desc));
     }
+    // The LLM sees the alias, results are still returned under the field name
+    if let Some(alias) = alias {
+        property.with_meta("alias", baml_types::BamlValue::String(alias));
+    }
+    if let Some(skip) = skip {
+        property.with_meta("skip", baml_types::BamlValue::Bool(skip));
+    }
 
     Ok(())
 }
@@ -285,6 +427,7 @@ fn parse_field_type<'a>(
             "int" => Ok(TypeIR::int()),
             "float" => Ok(TypeIR::float()),
             "bool" => Ok(TypeIR::bool()),
+            "null" => Ok(TypeIR::null()),
             _ => Ok(TypeIR::class(&atom_str)),
         }
     } else if let Ok(string_value) = term.decode::<String>() {
@@ -360,6 +503,53 @@ fn parse_field_type<'a>(
                 }
                 Err(Error::Term(Box::new("Could not extract list inner type")))
             }
+            Some("Elixir.BamlElixir.TypeBuilder.Optional") => {
+                // Extract the inner type, which may be left out by the LLM
+                let iter =
+                    MapIterator::new(term).ok_or(Error::Term(Box::new("Invalid optional map")))?;
+                for (key_term, value_term) in iter {
+                    let key = term_to_string(key_term)?;
+                    if key == "type" {
+                        let inner_type =
+                            parse_field_type(env, value_term, builder, parent_class, field_name)?;
+                        return Ok(TypeIR::optional(inner_type));
+                    }
+                }
+                Err(Error::Term(Box::new(
+                    "Could not extract optional inner type",
+                )))
+            }
+            Some("Elixir.BamlElixir.TypeBuilder.Constrained") => {
+                let iter = MapIterator::new(term)
+                    .ok_or(Error::Term(Box::new("Invalid constrained map")))?;
+                let mut inner_type = None;
+                let mut constraints = Vec::new();
+
+                for (key_term, value_term) in iter {
+                    let key = term_to_string(key_term)?;
+                    match key.as_str() {
+                        "type" => {
+                            inner_type = Some(parse_field_type(
+                                env,
+                                value_term,
+                                builder,
+                                parent_class,
+                                field_name,
+                            )?);
+                        }
+                        "constraints" => {
+                            constraints = parse_constraints(value_term)?;
+                        }
+                        _ => {}
+                    }
+                }
+
+                let mut inner_type = inner_type.ok_or(Error::Term(Box::new(
+                    "Could not extract constrained inner type",
+                )))?;
+                inner_type.meta_mut().constraints.extend(constraints);
+                Ok(inner_type)
+            }
             Some("Elixir.BamlElixir.TypeBuilder.Map") => {
                 // Extract key and value types from the map
                 let mut key_type = None;
@@ -498,6 +688,88 @@ fn parse_field_type<'a>(
     }
 }
 
+fn parse_constraints(term: Term) -> Result<Vec<Constraint>, Error> {
+    if is_nil(term) {
+        return Ok(Vec::new());
+    }
+    let list: Vec<Term> = term
+        .decode()
+        .map_err(|_| Error::Term(Box::new("Constraints must be a list")))?;
+    list.into_iter().map(parse_constraint).collect()
+}
+
+// `%TypeBuilder.Check{}` and `%TypeBuilder.Assert{}` are the equivalents of
+// `@check(name, {{ expression }})` and `@assert(name, {{ expression }})`.
+// Expressions refer to the value as `this`.
+fn parse_constraint(term: Term) -> Result<Constraint, Error> {
+    let iter = MapIterator::new(term).ok_or(Error::Term(Box::new("Invalid constraint map")))?;
+    let mut level = None;
+    let mut name = None;
+    let mut expression = None;
+
+    for (key_term, value_term) in iter {
+        let key = term_to_string(key_term)?;
+        match key.as_str() {
+            "__struct__" => {
+                level = match term_to_string(value_term)?.as_str() {
+                    "Elixir.BamlElixir.TypeBuilder.Check" => Some(ConstraintLevel::Check),
+                    "Elixir.BamlElixir.TypeBuilder.Assert" => Some(ConstraintLevel::Assert),
+                    other => {
+                        return Err(Error::Term(Box::new(format!(
+                            "Expected Check or Assert struct, got: {}",
+                            other
+                        ))))
+                    }
+                };
+            }
+            "name" => {
+                name = optional_string(value_term)?;
+            }
+            "expression" => {
+                expression = Some(term_to_string(value_term)?);
+            }
+            _ => {}
+        }
+    }
+
+    let level = level.ok_or(Error::Term(Box::new("Missing __struct__ field")))?;
+    let expression =
+        expression.ok_or(Error::Term(Box::new("Constraint missing expression field")))?;
+    // Checks are reported by name, so unlike asserts they must have one
+    if matches!(level, ConstraintLevel::Check) && name.is_none() {
+        return Err(Error::Term(Box::new("Check missing name field")));
+    }
+
+    Ok(Constraint {
+        level,
+        expression: JinjaExpression(expression),
+        label: name,
+    })
+}
+
+fn is_nil(term: Term) -> bool {
+    term.is_atom() && term.decode::<Atom>().ok() == Some(atom::nil())
+}
+
+// `nil` means the attribute is not set
+fn optional_string(term: Term) -> Result<Option<String>, Error> {
+    if is_nil(term) {
+        Ok(None)
+    } else {
+        term_to_string(term).map(Some)
+    }
+}
+
+fn optional_bool(term: Term) -> Result<Option<bool>, Error> {
+    if is_nil(term) {
+        Ok(None)
+    } else {
+        term.decode::<bool>()
+            .map(Some)
+            .map_err(|_| Error::Term(Box::new("Expected a boolean")))
+    }
+}
+
 // Helper function to convert a Term to a String
 fn term_to_string(term: Term) -> Result<String, Error> {
     if term.is_atom() {
@@ -21,7 +21,7 @@ defmodule BamlElixirTest do
     }
 
     # parse: false to avoid any parsing work; we want to exercise registry decoding/validation
-    assert {:error, msg} =
+    assert {:error, %{type: :invalid_argument, message: msg}} =
              BamlElixirTest.WhichModel.call(%{}, %{client_registry: client_registry, parse: false})
 
     assert msg =~ "Invalid client provider"
@@ -40,7 +40,7 @@ defmodule BamlElixirTest do
       }
     }
 
-    assert {:error, msg} =
+    assert {:error, %{type: :invalid_argument, message: msg}} =
              BamlElixirTest.WhichModel.call(%{}, %{client_registry: client_registry, parse: false})
 
     assert msg =~ "Invalid client provider"
@@ -168,6 +168,375 @@ defmodule BamlElixirTest do
     assert usage["cached_input_tokens"] == 0
   end
 
+  @tag :runtime
+  test "a compiled runtime can be reused across calls" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    client_registry = injected_client_registry(base_url)
+
+    baml_src = Path.join(System.tmp_dir!(), "baml_src_#{System.unique_integer([:positive])}")
+    File.cp_r!("test/baml_src", baml_src)
+    {:ok, runtime} = BamlElixir.Runtime.new(baml_src)
+
+    # The source is only read when the runtime is created, so calls keep working
+    # after it is gone, while calls that compile the path fail
+    File.rm_rf!(baml_src)
+
+    assert {:error, %{type: :configuration_error}} =
+             BamlElixir.Client.call("WhichModelUnion", %{}, %{
+               path: baml_src,
+               client_registry: client_registry
+             })
+
+    for _ <- 1..2 do
+      assert {:ok, "GPT4"} =
+               BamlElixir.Client.call("WhichModelUnion", %{}, %{
+                 path: baml_src,
+                 runtime: runtime,
+                 client_registry: client_registry
+               })
+    end
+  end
+
+  test "arguments the NIF cannot decode raise in the caller instead of hanging" do
+    opts = %{collectors: [%BamlElixir.Collector{reference: :not_a_collector}]}
+
+    assert_raise ArgumentError, fn -> BamlElixirTest.WhichModelUnion.call(%{}, opts) end
+
+    Process.flag(:trap_exit, true)
+    assert_raise ArgumentError, fn -> BamlElixirTest.WhichModelUnion.call(%{}, opts) end
+    refute_received {:EXIT, _, _}
+  end
+
+  @tag :runtime
+  test "an invalid runtime option raises an ArgumentError" do
+    assert_raise ArgumentError, ~r/expected :runtime to be a %BamlElixir.Runtime{}/, fn ->
+      BamlElixirTest.WhichModelUnion.call(%{}, %{runtime: "test/baml_src"})
+    end
+  end
+
+  @tag :runtime
+  test "runtime_new returns an error for an invalid baml_src" do
+    assert {:error, _message} = BamlElixir.Runtime.new("test/does_not_exist")
+  end
+
+  @tag :runtime
+  test "runtime can be built from in-memory BAML files" do
+    files = %{"main.baml" => File.read!("test/baml_src/baml_elixir_test.baml")}
+    {:ok, runtime} = BamlElixir.Runtime.from_files(files)
+
+    assert %{classes: %{"Person" => _}, functions: %{"ExtractPerson" => _}} =
+             BamlElixir.Runtime.parse(runtime)
+
+    assert %{functions: %{"ExtractPerson" => _}} = BamlElixir.Native.parse_baml(files)
+  end
+
+  @tag :runtime
+  test "runtimes without env read the environment on every call" do
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion("hi", %{
+        "authorization" => {:contains, "rotated-key"}
+      })
+
+    files = %{
+      "main.baml" => """
+      client<llm> EnvClient {
+        provider openai-generic
+        options {
+          base_url "#{base_url}"
+          api_key env.BAML_ELIXIR_TEST_ROTATED_KEY
+          model "gpt-4o-mini"
+        }
+      }
+
+      function SayHi() -> string {
+        client EnvClient
+        prompt #"Say hi"#
+      }
+      """
+    }
+
+    {:ok, runtime} = BamlElixir.Runtime.from_files(files)
+    System.put_env("BAML_ELIXIR_TEST_ROTATED_KEY", "rotated-key")
+    on_exit(fn -> System.delete_env("BAML_ELIXIR_TEST_ROTATED_KEY") end)
+
+    assert {:ok, "hi"} = BamlElixir.Client.call("SayHi", %{}, %{runtime: runtime})
+  end
+
+  @tag :async
+  test "a panic in an async task is replied to with an internal error" do
+    ref = make_ref()
+    assert :ok = BamlElixir.Native.spawn_panicking_task(self(), ref)
+
+    assert_receive {^ref, {:error, %{type: :internal_error, message: message}}}, 1000
+    assert message =~ "spawn_panicking_task"
+  end
+
+  @tag :async
+  test "async calls run concurrently without a dirty scheduler" do
+    delay_ms = 1_000
+
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4", %{}, %{delay_ms: delay_ms})
+
+    client_registry = injected_client_registry(base_url)
+
+    # Calls that held a dirty scheduler for the whole request would take at least
+    # two rounds of `delay_ms`
+    schedulers =
+      max(
+        :erlang.system_info(:dirty_cpu_schedulers_online),
+        :erlang.system_info(:dirty_io_schedulers)
+      )
+
+    calls = schedulers * 2
+    started_at = System.monotonic_time(:millisecond)
+
+    results =
+      1..calls
+      |> Task.async_stream(
+        fn _ ->
+          BamlElixirTest.WhichModelUnion.call(%{}, %{
+            client_registry: client_registry,
+            async: true
+          })
+        end,
+        max_concurrency: calls,
+        timeout: delay_ms * 10
+      )
+      |> Enum.map(fn {:ok, result} -> result end)
+
+    elapsed_ms = System.monotonic_time(:millisecond) - started_at
+
+    assert Enum.all?(results, &(&1 == {:ok, "GPT4"}))
+    assert elapsed_ms < delay_ms * 1.8
+  end
+
+  @tag :async
+  test "async streams deliver partial and final results" do
+    {base_url, bypass} =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream(["GP", "T4"])
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.sync_stream(%{}, fn _ -> :ok end, %{
+               client_registry: injected_client_registry(base_url),
+               async: true
+             })
+
+    Bypass.down(bypass)
+  end
+
+  @tag :errors
+  test "HTTP errors from the LLM provider are returned as client_http_error" do
+    bypass = Bypass.open()
+
+    Bypass.expect(bypass, "POST", "/v1/chat/completions", fn conn ->
+      Plug.Conn.send_resp(conn, 429, ~s({"error": {"message": "Rate limit reached"}}))
+    end)
+
+    assert {:error, %{type: :client_http_error, status_code: 429, client_name: "InjectedClient"}} =
+             BamlElixirTest.WhichModelUnion.call(%{}, %{
+               client_registry: injected_client_registry("http://localhost:#{bypass.port}/v1")
+             })
+  end
+
+  @tag :media
+  test "tagged media maps are sent as media arguments" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("A green ogre smiling")
+    collector = BamlElixir.Collector.new("test-collector", %{body: :text})
+
+    assert {:ok, "A green ogre smiling"} =
+             BamlElixirTest.DescribeImage.call(
+               %{
+                 myImg: %{
+                   __baml_media__: :image,
+                   url: "https://example.com/shrek.png",
+                   media_type: "image/png"
+                 }
+               },
+               %{client_registry: injected_client_registry(base_url), collectors: [collector]}
+             )
+
+    request_body =
+      BamlElixir.Collector.last_function_log(collector)["calls"]
+      |> Enum.at(0)
+      |> Map.get("request")
+      |> Map.get("body")
+
+    assert request_body =~ "https://example.com/shrek.png"
+  end
+
+  @tag :media
+  test "media from a local file is validated before sending the request" do
+    assert {:error, %{type: :invalid_argument, message: message}} =
+             BamlElixirTest.DescribeImage.call(%{
+               myImg: BamlElixir.Media.file(:image, "test/does_not_exist.png")
+             })
+
+    assert message =~ "Failed to read media file"
+
+    assert {:error, %{type: :invalid_argument, message: message}} =
+             BamlElixirTest.DescribeImage.call(
+               %{myImg: BamlElixir.Media.file(:image, "test/does_not_exist.png")},
+               %{async: true}
+             )
+
+    assert message =~ "Failed to read media file"
+
+    assert {:error, %{type: :invalid_argument, message: message}} =
+             BamlElixirTest.DescribeImage.call(%{
+               myImg: BamlElixir.Media.base64(:image, "not*base64", "image/png")
+             })
+
+    assert message =~ "Invalid base64 media"
+
+    assert {:error, %{type: :invalid_argument, message: message}} =
+             BamlElixirTest.DescribeImage.call(%{
+               myImg: BamlElixir.Media.url(:image, "https://example.com/a.pdf")
+             })
+
+    assert message =~ "application/pdf"
+  end
+
+  @tag :media
+  test "media from a local file is sent as base64" do
+    path = Path.join(System.tmp_dir!(), "baml_elixir_test_image.png")
+    File.write!(path, "not really a png")
+    on_exit(fn -> File.rm(path) end)
+
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("A tiny image")
+    collector = BamlElixir.Collector.new("test-collector", %{body: :text})
+
+    assert {:ok, "A tiny image"} =
+             BamlElixirTest.DescribeImage.call(
+               %{myImg: BamlElixir.Media.file(:image, path)},
+               %{client_registry: injected_client_registry(base_url), collectors: [collector]}
+             )
+
+    request_body =
+      BamlElixir.Collector.last_function_log(collector)["calls"]
+      |> Enum.at(0)
+      |> Map.get("request")
+      |> Map.get("body")
+
+    assert request_body =~ Base.encode64("not really a png")
+  end
+
+  @tag :checks
+  test "check results are returned alongside checked values" do
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion(~s({"quote": "Hello", "year": 1990}))
+
+    assert {:ok,
+            %BamlElixirTest.Citation{
+              quote: %{
+                value: "Hello",
+                checks: %{"not_empty" => %{name: "not_empty", status: "succeeded"}}
+              },
+              year: %{
+                value: 1990,
+                checks: %{"recent" => %{name: "recent", expression: _, status: "failed"}}
+              }
+            }} =
+             BamlElixirTest.ExtractCitation.call(%{text: "Hello, said in 1990"}, %{
+               client_registry: injected_client_registry(base_url)
+             })
+  end
+
+  @tag :stream_state
+  test "partial results carry the state of @stream.with_state fields" do
+    {base_url, bypass} =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream([
+        ~s({"title": "Elixir", "sections": ["Intro"),
+        ~s(, "OTP"]})
+      ])
+
+    pid = self()
+
+    assert {:ok, %BamlElixirTest.Outline{title: "Elixir", sections: ["Intro", "OTP"]}} =
+             BamlElixirTest.WriteOutline.sync_stream(
+               %{topic: "Elixir"},
+               fn partial -> send(pid, {:partial, partial}) end,
+               %{client_registry: injected_client_registry(base_url)}
+             )
+
+    assert_received {:partial,
+                     %BamlElixirTest.Outline{
+                       title: "Elixir",
+                       sections: %{value: ["Intro"], state: :incomplete}
+                     }}
+
+    Bypass.down(bypass)
+  end
+
+  @tag :stream_state
+  test "stream_state: true attaches the state to every partial value" do
+    {base_url, bypass} =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream([
+        ~s({"title": "Elixir", "sections": ["Intro"),
+        ~s(, "OTP"]})
+      ])
+
+    pid = self()
+
+    assert {:ok, %BamlElixirTest.Outline{title: "Elixir", sections: ["Intro", "OTP"]}} =
+             BamlElixirTest.WriteOutline.sync_stream(
+               %{topic: "Elixir"},
+               fn partial -> send(pid, {:partial, partial}) end,
+               %{client_registry: injected_client_registry(base_url), stream_state: true}
+             )
+
+    assert_received {:partial,
+                     %{
+                       value: %BamlElixirTest.Outline{
+                         title: %{value: "Elixir", state: :complete},
+                         sections: %{value: [%{value: "Intro"} | _], state: :incomplete}
+                       },
+                       state: :incomplete
+                     }}
+
+    Bypass.down(bypass)
+  end
+
+  @tag :on_tick
+  test "on_tick receives the raw text and function log of a call" do
+    {base_url, bypass} =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream(["GP", "T4"])
+    pid = self()
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               on_tick: fn info -> send(pid, {:tick, info}) end
+             })
+
+    assert_received {:tick,
+                     %{
+                       raw_text: raw_text,
+                       function_log: %{"function_name" => "WhichModelUnion"},
+                       thinking: []
+                     }}
+
+    assert is_binary(raw_text)
+    Bypass.down(bypass)
+  end
+
+  @tag :on_tick
+  test "returning :abort from on_tick cancels the stream" do
+    {base_url, bypass} =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream(%{
+        chunks: ["GP", "T", "4"],
+        delay_ms: 200
+      })
+
+    assert {:error, %{type: :cancelled}} =
+             BamlElixirTest.WhichModelUnion.sync_stream(%{}, fn _ -> :ok end, %{
+               client_registry: injected_client_registry(base_url),
+               on_tick: fn _info -> :abort end
+             })
+
+    Bypass.down(bypass)
+  end
+
   test "parses into a struct" do
     assert {:ok, %BamlElixirTest.Person{name: "John Doe", age: 28}} =
              BamlElixirTest.ExtractPerson.call(%{info: "John Doe, 28, Engineer"})
@@ -265,6 +634,235 @@ defmodule BamlElixirTest do
              })
   end
 
+  test "type builder aliases and skips fields and enum values" do
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion(
+        ~s({"employee_id": "1", "person": {"full_name": "Ada", "color": "crimson"}})
+      )
+
+    collector = BamlElixir.Collector.new("alias-collector", %{body: :text})
+
+    assert {:ok, %{person: %{name: "Ada", color: :RED} = person}} =
+             BamlElixirTest.CreateEmployee.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               collectors: [collector],
+               tb: [
+                 %TypeBuilder.Class{
+                   name: "NewEmployeeFullyDynamic",
+                   fields: [
+                     %TypeBuilder.Field{
+                       name: "person",
+                       type: %TypeBuilder.Class{
+                         name: "AliasedPerson",
+                         fields: [
+                           %TypeBuilder.Field{name: "name", type: :string, alias: "full_name"},
+                           %TypeBuilder.Field{name: "internal_id", type: :string, skip: true},
+                           %TypeBuilder.Field{
+                             name: "color",
+                             type: %TypeBuilder.Enum{
+                               name: "AliasedColor",
+                               values: [
+                                 %TypeBuilder.EnumValue{value: "RED", alias: "crimson"},
+                                 %TypeBuilder.EnumValue{value: "GREEN", skip: true},
+                                 %TypeBuilder.EnumValue{value: "BLUE"}
+                               ]
+                             }
+                           }
+                         ]
+                       }
+                     }
+                   ]
+                 }
+               ]
+             })
+
+    refute Map.has_key?(person, :internal_id)
+
+    %{"calls" => [%{"request" => %{"body" => prompt}}]} =
+      BamlElixir.Collector.last_function_log(collector)
+
+    assert prompt =~ "full_name"
+    assert prompt =~ "crimson"
+    refute prompt =~ "GREEN"
+    refute prompt =~ "internal_id"
+  end
+
+  test "type builder optional and null fields may be left out" do
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion(
+        ~s({"employee_id": "1", "person": {"name": "Ada", "middle_name": null}})
+      )
+
+    assert {:ok, %{person: %{name: "Ada", nickname: nil, middle_name: nil}}} =
+             BamlElixirTest.CreateEmployee.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               tb: [
+                 %TypeBuilder.Class{
+                   name: "NewEmployeeFullyDynamic",
+                   fields: [
+                     %TypeBuilder.Field{
+                       name: "person",
+                       type: %TypeBuilder.Class{
+                         name: "OptionalPerson",
+                         fields: [
+                           %TypeBuilder.Field{name: "name", type: :string},
+                           %TypeBuilder.Field{
+                             name: "nickname",
+                             type: %TypeBuilder.Optional{type: :string}
+                           },
+                           %TypeBuilder.Field{
+                             name: "middle_name",
+                             type: %TypeBuilder.Union{types: [:string, :null]}
+                           }
+                         ]
+                       }
+                     }
+                   ]
+                 }
+               ]
+             })
+  end
+
+  test "type builder classes and enums carry descriptions and aliases" do
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion(
+        ~s({"employee_id": "1", "person": {"name": "Ada", "team": "core"}})
+      )
+
+    collector = BamlElixir.Collector.new("class-meta-collector", %{body: :text})
+
+    assert {:ok, %{person: %{name: "Ada", team: :CORE}}} =
+             BamlElixirTest.CreateEmployee.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               collectors: [collector],
+               tb: [
+                 %TypeBuilder.Enum{
+                   name: "DescribedTeam",
+                   description: "The team the person works in",
+                   alias: "Team",
+                   values: [
+                     %TypeBuilder.EnumValue{value: "CORE", alias: "core"},
+                     %TypeBuilder.EnumValue{value: "INFRA", alias: "infra"}
+                   ]
+                 },
+                 %TypeBuilder.Class{
+                   name: "NewEmployeeFullyDynamic",
+                   fields: [
+                     %TypeBuilder.Field{
+                       name: "person",
+                       type: %TypeBuilder.Class{
+                         name: "DescribedPerson",
+                         description: "A person who was hired this year",
+                         alias: "Hire",
+                         fields: [
+                           %TypeBuilder.Field{name: "name", type: :string},
+                           %TypeBuilder.Field{
+                             name: "team",
+                             type: %TypeBuilder.Enum{name: "DescribedTeam"}
+                           }
+                         ]
+                       }
+                     }
+                   ]
+                 }
+               ]
+             })
+
+    %{"calls" => [%{"request" => %{"body" => prompt}}]} =
+      BamlElixir.Collector.last_function_log(collector)
+
+    assert prompt =~ "A person who was hired this year"
+  end
+
+  test "type builder fields and types carry checks and asserts" do
+    tb = [
+      %TypeBuilder.Class{
+        name: "NewEmployeeFullyDynamic",
+        fields: [
+          %TypeBuilder.Field{
+            name: "age",
+            type: :int,
+            constraints: [%TypeBuilder.Check{name: "adult", expression: "this >= 18"}]
+          },
+          %TypeBuilder.Field{
+            name: "scores",
+            type: %TypeBuilder.List{
+              type: %TypeBuilder.Constrained{
+                type: :int,
+                constraints: [%TypeBuilder.Assert{name: "positive", expression: "this > 0"}]
+              }
+            }
+          }
+        ]
+      }
+    ]
+
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion(
+        ~s({"employee_id": "1", "age": 16, "scores": [1, 2]})
+      )
+
+    assert {:ok,
+            %{
+              age: %{value: 16, checks: %{"adult" => %{name: "adult", status: "failed"}}},
+              scores: [1, 2]
+            }} =
+             BamlElixirTest.CreateEmployee.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               tb: tb
+             })
+
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion(
+        ~s({"employee_id": "1", "age": 30, "scores": [1, -2]})
+      )
+
+    assert {:error, %{type: :validation_error}} =
+             BamlElixirTest.CreateEmployee.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               tb: tb
+             })
+  end
+
+  test "type builder accepts BAML source" do
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion(
+        ~s({"employee_id": "1", "person": {"name": "Ada", "level": "SENIOR"}})
+      )
+
+    source = """
+    enum SourceLevel {
+      JUNIOR
+      SENIOR
+    }
+
+    class SourcePerson {
+      name string
+      level SourceLevel
+    }
+
+    dynamic class NewEmployeeFullyDynamic {
+      person SourcePerson
+    }
+    """
+
+    assert {:ok, %{person: %{name: "Ada", level: :SENIOR}}} =
+             BamlElixirTest.CreateEmployee.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               tb: [%TypeBuilder.Baml{source: source}]
+             })
+  end
+
+  test "invalid type builder BAML source returns diagnostics" do
+    assert {:error, %{type: :type_builder_error, diagnostics: [diagnostic | _]}} =
+             BamlElixirTest.CreateEmployee.call(%{}, %{
+               tb: [%TypeBuilder.Baml{source: "clas Broken {\n  name string\n}"}]
+             })
+
+    assert %{severity: :error, message: message, line: 1} = diagnostic
+    assert message =~ "does not start with any known Baml schema keyword"
+  end
+
   test "parses type builder with nested types" do
     assert {:ok,
             %{
@@ -347,7 +945,33 @@ defmodule BamlElixirTest do
   end
 
   test "Error when parsing the output of a function" do
-    assert {:error, "Failed to coerce value" <> _} = BamlElixirTest.DummyOutputFunction.call(%{})
+    assert {:error, %{type: :validation_error, message: message, raw_output: raw_output}} =
+             BamlElixirTest.DummyOutputFunction.call(%{})
+
+    assert message =~ "Failed to coerce value"
+    assert is_binary(raw_output)
+  end
+
+  @tag :errors
+  test "parse failures include the raw output, prompt, client and diagnostics" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("hello there")
+
+    assert {:error,
+            %{
+              type: :validation_error,
+              message: message,
+              raw_output: "hello there",
+              prompt: prompt,
+              client_name: "InjectedClient",
+              diagnostics: diagnostics
+            }} =
+             BamlElixirTest.DummyOutputFunction.call(%{}, %{
+               client_registry: injected_client_registry(base_url)
+             })
+
+    assert prompt =~ "Say \"hello there\""
+    assert [%{severity: :error, line: nil} | _] = diagnostics
+    assert Enum.all?(diagnostics, &(is_binary(&1.message) and &1.message != message))
   end
 
   test "get usage from collector" do
@@ -391,7 +1015,6 @@ defmodule BamlElixirTest do
       |> Enum.at(0)
       |> Map.get("response")
       |> Map.get("body")
-      |> Jason.decode!()
 
     assert response_body["usage"]["prompt_tokens_details"] == %{
              "audio_tokens" => 0,
@@ -402,13 +1025,418 @@ defmodule BamlElixirTest do
              "calls",
              "function_name",
              "id",
+             "inferred_strategy",
              "log_type",
              "raw_llm_response",
+             "selected_call",
+             "tags",
              "timing",
              "usage"
            ]
   end
 
+  @tag :collector
+  test "collector usage and calls include cost from the price table" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+
+    collector =
+      BamlElixir.Collector.new("priced-collector", %{
+        prices: %{
+          "openai-generic" => %{"gpt-4o-mini" => %{input: 1_000_000, output: 2_000_000}}
+        }
+      })
+
+    unpriced =
+      BamlElixir.Collector.new("unpriced-collector", %{
+        prices: %{"openai-generic" => %{"gpt-4o" => %{input: 2.5, output: 10}}}
+      })
+
+    opts = %{
+      client_registry: injected_client_registry(base_url),
+      collectors: [collector, unpriced]
+    }
+
+    assert {:ok, "GPT4"} = BamlElixirTest.WhichModelUnion.call(%{}, opts)
+
+    assert BamlElixir.Collector.usage(collector)["cost"] == 3.0
+    %{"usage" => usage, "calls" => [call]} = BamlElixir.Collector.last_function_log(collector)
+    assert usage["cost"] == 3.0
+    assert call["usage"]["cost"] == 3.0
+
+    assert BamlElixir.Collector.usage(unpriced)["cost"] == nil
+  end
+
+  @tag :collector
+  test "the cost of a successful call without usage is unknown" do
+    {base_url, bypass} = BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream(["GPT4"])
+
+    collector =
+      BamlElixir.Collector.new("stream-priced-collector", %{
+        prices: %{"openai-generic" => %{"gpt-4o-mini" => %{input: 1, output: 2}}}
+      })
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.sync_stream(%{}, fn _ -> :ok end, %{
+               client_registry: injected_client_registry(base_url),
+               collectors: [collector]
+             })
+
+    assert BamlElixir.Collector.usage(collector)["cost"] == nil
+    assert BamlElixir.Collector.last_function_log(collector)["usage"]["cost"] == nil
+    Bypass.down(bypass)
+  end
+
+  @tag :collector
+  test "anthropic cache reads are priced on top of the input tokens" do
+    base_url =
+      BamlElixirTest.FakeOpenAIServer.expect_anthropic_message("GPT4", %{
+        input_tokens: 1,
+        cache_read_input_tokens: 2,
+        output_tokens: 1
+      })
+
+    collector =
+      BamlElixir.Collector.new("anthropic-collector", %{
+        prices: %{
+          "anthropic" => %{
+            "claude-sonnet-4-20250514" => %{
+              input: 1_000_000,
+              cached_input: 500_000,
+              output: 2_000_000
+            }
+          }
+        }
+      })
+
+    client_registry = %{
+      primary: "Claude",
+      clients: [
+        %{
+          name: "Claude",
+          provider: "anthropic",
+          retry_policy: nil,
+          options: %{
+            base_url: base_url,
+            api_key: "test-key",
+            model: "claude-sonnet-4-20250514"
+          }
+        }
+      ]
+    }
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.call(%{}, %{
+               client_registry: client_registry,
+               collectors: [collector]
+             })
+
+    # 1 input token, 2 cache reads at half the input price and 1 output token
+    assert BamlElixir.Collector.usage(collector)["cost"] == 4.0
+  end
+
+  @tag :collector
+  test "collector keeps every function log in order" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    collector = BamlElixir.Collector.new("history-collector")
+    opts = %{client_registry: injected_client_registry(base_url), collectors: [collector]}
+
+    assert {:ok, "GPT4"} = BamlElixirTest.WhichModelUnion.call(%{}, opts)
+    assert {:ok, "GPT4"} = BamlElixirTest.WhichModelUnion.call(%{}, opts)
+
+    assert [first, second] = BamlElixir.Collector.logs(collector)
+    assert first["function_name"] == "WhichModelUnion"
+    assert second == BamlElixir.Collector.last_function_log(collector)
+    assert BamlElixir.Collector.log_by_id(collector, first["id"]) == first
+    assert BamlElixir.Collector.log_by_id(collector, "unknown") == nil
+  end
+
+  @tag :collector
+  test "tags are recorded on function logs and filter collector logs" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    collector = BamlElixir.Collector.new("tags-collector")
+    opts = %{client_registry: injected_client_registry(base_url), collectors: [collector]}
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.call(
+               %{},
+               Map.put(opts, :tags, %{"user_id" => "1", "feature" => "chat"})
+             )
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.call(%{}, Map.put(opts, :tags, %{user_id: "2"}))
+
+    assert [first, second] = BamlElixir.Collector.logs(collector)
+    assert first["tags"] == %{"user_id" => "1", "feature" => "chat"}
+    assert second["tags"] == %{"user_id" => "2"}
+
+    assert BamlElixir.Collector.logs(collector, %{"user_id" => "2"}) == [second]
+    assert BamlElixir.Collector.logs(collector, %{"feature" => "chat"}) == [first]
+    assert BamlElixir.Collector.logs(collector, %{"user_id" => "3"}) == []
+  end
+
+  @tag :collector
+  test "meta mode returns the trace id of the function log" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    collector = BamlElixir.Collector.new("meta-collector")
+
+    opts = %{
+      client_registry: injected_client_registry(base_url),
+      collectors: [collector],
+      meta: true
+    }
+
+    assert {:ok, "GPT4", %{trace_id: call_id}} = BamlElixirTest.WhichModelUnion.call(%{}, opts)
+
+    assert {:ok, "GPT4", %{trace_id: stream_id}} =
+             BamlElixirTest.WhichModelUnion.sync_stream(%{}, fn _ -> :ok end, opts)
+
+    assert [%{"id" => ^call_id}, %{"id" => ^stream_id}] = BamlElixir.Collector.logs(collector)
+    assert %{"function_name" => "WhichModelUnion"} =
+             BamlElixir.Collector.log_by_id(collector, call_id)
+  end
+
+  @tag :collector
+  test "collectors can be drained, cleared and limited to the latest logs" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    collector = BamlElixir.Collector.new("window-collector", %{max_logs: 2})
+    opts = %{client_registry: injected_client_registry(base_url), collectors: [collector]}
+
+    for _ <- 1..3, do: assert({:ok, "GPT4"} = BamlElixirTest.WhichModelUnion.call(%{}, opts))
+
+    assert [_, _] = logs = BamlElixir.Collector.logs(collector)
+    assert BamlElixir.Collector.drain(collector) == logs
+    assert BamlElixir.Collector.logs(collector) == []
+
+    assert {:ok, "GPT4"} = BamlElixirTest.WhichModelUnion.call(%{}, opts)
+    assert BamlElixir.Collector.clear(collector) == 1
+    assert BamlElixir.Collector.last_function_log(collector) == nil
+  end
+
+  @tag :collector
+  test "function logs show which fallback client produced the result" do
+    broken = Bypass.open()
+
+    Bypass.expect(broken, "POST", "/v1/chat/completions", fn conn ->
+      Plug.Conn.send_resp(conn, 500, ~s({"error": {"message": "Internal error"}}))
+    end)
+
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    %{clients: [working]} = injected_client_registry(base_url)
+    broken_client = %{working | name: "BrokenClient"}
+    broken_client = put_in(broken_client.options.base_url, "http://localhost:#{broken.port}/v1")
+
+    client_registry = %{
+      primary: "Fallback",
+      clients: [
+        broken_client,
+        working,
+        %{
+          name: "Fallback",
+          provider: "fallback",
+          retry_policy: nil,
+          options: %{strategy: ["BrokenClient", "InjectedClient"]}
+        }
+      ]
+    }
+
+    collector = BamlElixir.Collector.new("fallback-collector")
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.call(%{}, %{
+               client_registry: client_registry,
+               collectors: [collector]
+             })
+
+    log = BamlElixir.Collector.last_function_log(collector)
+
+    assert %{"type" => "fallback", "clients" => ["BrokenClient", "InjectedClient"]} =
+             log["inferred_strategy"]
+
+    assert [
+             %{"selected" => false, "error" => "HTTP 500" <> _},
+             %{"selected" => true} = selected
+           ] = log["calls"]
+
+    assert log["selected_call"] == selected
+    assert selected["finish_reason"] == "stop"
+  end
+
+  @tag :collector
+  test "stream calls report time to first token and chunk count" do
+    {base_url, bypass} =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream(%{
+        chunks: ["GP", "T4"],
+        delay_ms: 50
+      })
+
+    collector = BamlElixir.Collector.new("stream-timing-collector")
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.sync_stream(%{}, fn _ -> :ok end, %{
+               client_registry: injected_client_registry(base_url),
+               collectors: [collector]
+             })
+
+    %{"selected_call" => %{"timing" => timing}} =
+      BamlElixir.Collector.last_function_log(collector)
+
+    assert %{
+             "time_to_first_token_ms" => ttft,
+             "time_to_first_parsed_ms" => ttfp,
+             "chunk_count" => chunk_count
+           } = timing
+
+    assert ttft >= 0 and ttfp >= ttft
+    assert chunk_count >= 2
+    Bypass.down(bypass)
+  end
+
+  @tag :collector
+  test "stream timing is measured for each fallback call on its own" do
+    broken = Bypass.open()
+
+    Bypass.expect(broken, "POST", "/v1/chat/completions", fn conn ->
+      Process.sleep(200)
+      Plug.Conn.send_resp(conn, 500, ~s({"error": {"message": "Internal error"}}))
+    end)
+
+    {base_url, bypass} =
+      BamlElixirTest.FakeOpenAIServer.expect_chat_completion_stream(%{
+        chunks: ["GP", "T4"],
+        delay_ms: 50
+      })
+
+    %{clients: [working]} = injected_client_registry(base_url)
+    broken_client = %{working | name: "BrokenClient"}
+    broken_client = put_in(broken_client.options.base_url, "http://localhost:#{broken.port}/v1")
+
+    client_registry = %{
+      primary: "Fallback",
+      clients: [
+        broken_client,
+        working,
+        %{
+          name: "Fallback",
+          provider: "fallback",
+          retry_policy: nil,
+          options: %{strategy: ["BrokenClient", "InjectedClient"]}
+        }
+      ]
+    }
+
+    collector = BamlElixir.Collector.new("fallback-stream-collector")
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.sync_stream(%{}, fn _ -> :ok end, %{
+               client_registry: client_registry,
+               collectors: [collector]
+             })
+
+    assert [
+             %{"selected" => false, "timing" => failed_timing},
+             %{"selected" => true, "timing" => selected_timing}
+           ] = BamlElixir.Collector.last_function_log(collector)["calls"]
+
+    assert %{"time_to_first_token_ms" => nil, "chunk_count" => 0} = failed_timing
+
+    assert %{"time_to_first_token_ms" => ttft, "chunk_count" => chunk_count} =
+             selected_timing
+
+    assert ttft >= 0 and ttft < 200
+    assert chunk_count >= 2
+    Bypass.down(bypass)
+  end
+
+  @tag :collector
+  test "request and response bodies are decoded JSON, raw or capped" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    json_collector = BamlElixir.Collector.new("json-collector")
+    raw_collector = BamlElixir.Collector.new("raw-collector", %{body: :raw, max_body_bytes: 10})
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.call(%{}, %{
+               client_registry: injected_client_registry(base_url),
+               collectors: [json_collector, raw_collector]
+             })
+
+    [json_call] = BamlElixir.Collector.last_function_log(json_collector)["calls"]
+    assert %{"model" => "gpt-4o-mini", "messages" => [_ | _]} = json_call["request"]["body"]
+    assert %{"choices" => [%{"message" => %{"content" => "GPT4"}}]} =
+             json_call["response"]["body"]
+    assert json_call["request"]["body_truncated"] == false
+
+    [raw_call] = BamlElixir.Collector.last_function_log(raw_collector)["calls"]
+    assert byte_size(raw_call["request"]["body"]) == 10
+    assert raw_call["request"]["body_truncated"] == true
+    assert raw_call["request"]["body_size"] == json_call["request"]["body_size"]
+  end
+
+  @tag :collector
+  test "collectors redact credentials unless disabled" do
+    base_url = BamlElixirTest.FakeOpenAIServer.expect_chat_completion("GPT4")
+    %{clients: [client]} = client_registry = injected_client_registry(base_url)
+    client = put_in(client.options[:headers], %{"x-tenant-token" => "secret"})
+    client_registry = %{client_registry | clients: [client]}
+
+    redacting =
+      BamlElixir.Collector.new("redacting-collector", %{
+        redact_headers: ["X-Tenant-Token"],
+        redact_body_paths: ["messages.content"]
+      })
+
+    debugging = BamlElixir.Collector.new("debugging-collector", %{redact: false})
+
+    assert {:ok, "GPT4"} =
+             BamlElixirTest.WhichModelUnion.call(%{}, %{
+               client_registry: client_registry,
+               collectors: [redacting, debugging]
+             })
+
+    [call] = BamlElixir.Collector.last_function_log(redacting)["calls"]
+    assert call["request"]["headers"]["authorization"] == "[REDACTED]"
+    assert call["request"]["headers"]["x-tenant-token"] == "[REDACTED]"
+    assert Enum.all?(call["request"]["body"]["messages"], &(&1["content"] == "[REDACTED]"))
+
+    [call] = BamlElixir.Collector.last_function_log(debugging)["calls"]
+    assert call["request"]["headers"]["authorization"] == "Bearer test-key"
+    assert call["request"]["headers"]["x-tenant-token"] == "secret"
+  end
+
+  @tag :collector
+  test "body paths are redacted before bodies are cut and in HTTP errors" do
+    bypass = Bypass.open()
+
+    Bypass.expect(bypass, "POST", "/v1/chat/completions", fn conn ->
+      Plug.Conn.send_resp(conn, 500, ~s({"error": {"message": "secret internal error"}}))
+    end)
+
+    collector =
+      BamlElixir.Collector.new("capped-redacting-collector", %{
+        max_body_bytes: 50,
+        redact_body_paths: ["messages.content", "error.message"]
+      })
+
+    assert {:error, %{type: :client_http_error}} =
+             BamlElixirTest.WhichModelUnion.call(%{}, %{
+               client_registry: injected_client_registry("http://localhost:#{bypass.port}/v1"),
+               collectors: [collector]
+             })
+
+    [call] = BamlElixir.Collector.last_function_log(collector)["calls"]
+    assert call["request"]["body_truncated"] == true
+    assert call["request"]["body"] =~ "[REDACTED]"
+    refute call["request"]["body"] =~ "GPT4"
+    assert call["error"] =~ "[REDACTED]"
+    refute call["error"] =~ "secret internal error"
+
+    assert_raise ArgumentError, fn ->
+      BamlElixir.Collector.new("raw-redacting-collector", %{
+        body: :raw,
+        redact_body_paths: ["messages.content"]
+      })
+    end
+  end
+
   test "get last function log from collector with streaming" do
     collector = BamlElixir.Collector.new("test-collector")
     pid = self()
@@ -428,7 +1456,6 @@ defmodule BamlElixirTest do
       |> Enum.at(0)
       |> Map.get("request")
       |> Map.get("body")
-      |> Jason.decode!()
 
     assert messages == [
              %{
@@ -519,6 +1546,24 @@ defmodule BamlElixirTest do
            } = result
   end
 
+  defp injected_client_registry(base_url) do
+    %{
+      primary: "InjectedClient",
+      clients: [
+        %{
+          name: "InjectedClient",
+          provider: "openai-generic",
+          retry_policy: nil,
+          options: %{
+            base_url: base_url,
+            api_key: "test-key",
+            model: "gpt-4o-mini"
+          }
+        }
+      ]
+    }
+  end
+
   defp build_tool_type(tool_names) when is_list(tool_names) do
     tool_union = %TypeBuilder.Union{
       types: Enum.map(tool_names, fn name -> %TypeBuilder.Class{name: name} end)
@@ -574,7 +1619,7 @@ defmodule BamlElixirTest do
     end
   end
 
-  describe "stream cancellation" do
+  describe "cancellation" do
     import Mox
 
     setup [:set_mox_global, :verify_on_exit!]
@@ -604,7 +1649,8 @@ defmodule BamlElixirTest do
                                               _path,
                                               _collectors,
                                               _registry,
-                                              _tb ->
+                                              _tb,
+                                              _options ->
         send(test_pid, :stream_started)
 
         spawn(fn ->
@@ -633,5 +1679,68 @@ defmodule BamlElixirTest do
       Process.exit(caller_pid, :kill)
       assert_receive :abort_called, 1000
     end
+
+    @tag :call_cancellation
+    test "killing caller process during call calls abort_tripwire" do
+      test_pid = self()
+      tripwire_ref = make_ref()
+
+      stub(BamlElixir.NativeMock, :create_tripwire, fn -> tripwire_ref end)
+
+      expect(BamlElixir.NativeMock, :abort_tripwire, fn ^tripwire_ref ->
+        send(test_pid, :abort_called)
+        :ok
+      end)
+
+      stub(BamlElixir.NativeMock, :call, fn _pid,
+                                            _ref,
+                                            ^tripwire_ref,
+                                            _fn,
+                                            _args,
+                                            _path,
+                                            _collectors,
+                                            _registry,
+                                            _tb,
+                                            _options ->
+        send(test_pid, :call_started)
+
+        receive do
+          :finish -> {:ok, "result"}
+        end
+      end)
+
+      caller_pid =
+        spawn(fn ->
+          BamlElixir.Client.call("TestFunction", %{}, %{path: "test/baml_src"})
+        end)
+
+      assert_receive :call_started, 1000
+      Process.exit(caller_pid, :kill)
+      assert_receive :abort_called, 1000
+    end
+
+    @tag :call_cancellation
+    test "completed call does not abort the tripwire" do
+      tripwire_ref = make_ref()
+
+      stub(BamlElixir.NativeMock, :create_tripwire, fn -> tripwire_ref end)
+      expect(BamlElixir.NativeMock, :abort_tripwire, 0, fn _ -> :ok end)
+
+      stub(BamlElixir.NativeMock, :call, fn _pid,
+                                            _ref,
+                                            ^tripwire_ref,
+                                            _fn,
+                                            _args,
+                                            _path,
+                                            _collectors,
+                                            _registry,
+                                            _tb,
+                                            _options ->
+        {:ok, "result"}
+      end)
+
+      assert {:ok, "result"} =
+               BamlElixir.Client.call("TestFunction", %{}, %{path: "test/baml_src", parse: false})
+    end
   end
 end
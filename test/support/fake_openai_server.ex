@@ -15,6 +15,8 @@ defmodule BamlElixirTest.FakeOpenAIServer do
   ## Options
   - `response_content` - The content string to return in the response
   - `expected_headers` - Map of headers to validate (optional)
+  - `opts` - `cached_tokens` to report in the usage and `delay_ms` to wait before
+    responding (optional)
 
   ## Example
       base_url = FakeOpenAIServer.expect_chat_completion("Hello!")
@@ -24,11 +26,13 @@ defmodule BamlElixirTest.FakeOpenAIServer do
   def expect_chat_completion(response_content, expected_headers \\ %{}, opts \\ %{})
       when is_binary(response_content) and is_map(expected_headers) and is_map(opts) do
     cached_tokens = Map.get(opts, :cached_tokens, 0)
+    delay_ms = Map.get(opts, :delay_ms, 0)
 
     bypass = Bypass.open()
 
     Bypass.expect(bypass, "POST", "/v1/chat/completions", fn conn ->
       validate_headers!(conn, expected_headers)
+      if delay_ms > 0, do: Process.sleep(delay_ms)
 
       body =
         Jason.encode!(%{
@@ -54,6 +58,48 @@ defmodule BamlElixirTest.FakeOpenAIServer do
     "http://localhost:#{bypass.port}/v1"
   end
 
+  @doc """
+  Starts a Bypass server and sets up expectation for an Anthropic messages request.
+  Returns a base_url suitable for the `anthropic` provider.
+
+  ## Options
+  - `response_content` - The content string to return in the response
+  - `usage` - The usage to report, e.g. `%{input_tokens: 1, cache_read_input_tokens: 2}`
+  """
+  @spec expect_anthropic_message(String.t(), map()) :: String.t()
+  def expect_anthropic_message(response_content, usage \\ %{}) do
+    bypass = Bypass.open()
+
+    Bypass.expect(bypass, "POST", "/v1/messages", fn conn ->
+      body =
+        Jason.encode!(%{
+          "id" => "msg_test",
+          "type" => "message",
+          "role" => "assistant",
+          "model" => "claude-sonnet-4-20250514",
+          "content" => [%{"type" => "text", "text" => response_content}],
+          "stop_reason" => "end_turn",
+          "stop_sequence" => nil,
+          "usage" =>
+            Map.merge(
+              %{
+                "input_tokens" => 1,
+                "output_tokens" => 1,
+                "cache_creation_input_tokens" => 0,
+                "cache_read_input_tokens" => 0
+              },
+              Map.new(usage, fn {key, value} -> {to_string(key), value} end)
+            )
+        })
+
+      conn
+      |> put_resp_content_type("application/json")
+      |> send_resp(200, body)
+    end)
+
+    "http://localhost:#{bypass.port}"
+  end
+
   @doc """
   Starts a Bypass server and sets up expectation for a streaming chat completion request.
   Returns `{base_url, bypass}` where bypass can be used to manage the server lifecycle.